@@ -0,0 +1,231 @@
+//! Addresses that point at a single task
+//!
+//! A task can be addressed by position, e.g. `2024-03-07/Work/3` is the third task of section Work
+//! on 2024-03-07 and `2024-03-07/Work/3.1` is its first subtask (tasks of anonymous sections are
+//! `2024-03-07//1`). Positions change when tasks are added or moved, so a task can also be
+//! addressed by a short hash of its date, section, text and the text of the tasks above it, e.g.
+//! `5f3a09c1`, which stays the same when the task is moved within its section or its status
+//! changes. Tasks with the same text below the same task also hash how many of them come before,
+//! so every task has its own hash. Any unique start of the hash with at least 4 characters works
+//! too.
+
+use crate::{err, section::Section, task::Task, util::Result, Error};
+use chrono::NaiveDate;
+use std::{fmt, str};
+
+/// Number of hex characters of the content hash that are printed
+const HASH_LEN: usize = 8;
+/// Shortest start of a hash accepted as an address
+const MIN_HASH_LEN: usize = 4;
+
+#[derive(PartialEq, Eq, Debug, Clone, Hash)]
+pub enum Address {
+    /// Date, section name and position of the task in the section starting at 1, followed by the
+    /// position of subtasks in their parent
+    Position {
+        date: NaiveDate,
+        section: String,
+        position: Vec<usize>,
+    },
+    /// Start of the content hash of a task, see [content_hash]
+    Hash(String),
+}
+
+/// Short hash of the date, section name and texts of the task at position in section and of the
+/// tasks above it, used to address it
+pub fn content_hash(date: NaiveDate, section: &Section, position: &[usize]) -> String {
+    let mut key = format!("{date}/{}", section.name);
+    let mut tasks = &section.tasks[..];
+    for index in position {
+        let Some(index) = index.checked_sub(1).filter(|index| *index < tasks.len()) else {
+            break;
+        };
+        let task = &tasks[index];
+        key.push('/');
+        key.push_str(&task.text);
+        // the first task with a text hashes as if it were the only one
+        let before = tasks[..index]
+            .iter()
+            .filter(|t| t.text == task.text)
+            .count();
+        if before > 0 {
+            key.push_str(&format!("#{}", before + 1));
+        }
+        tasks = &task.subtasks;
+    }
+    let hash = stable_hash(&key);
+    format!("{hash:016x}")[..HASH_LEN].to_string()
+}
+
+/// FNV-1a hash of s, std's hasher is not guaranteed to give the same result across releases
+pub(crate) fn stable_hash(s: &str) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in s.bytes() {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+/// Every task in tasks (and subtasks) with its position, depth first
+pub(crate) fn positions(tasks: &[Task]) -> Vec<(Vec<usize>, &Task)> {
+    let mut positions = Vec::new();
+    for (index, task) in tasks.iter().enumerate() {
+        positions.push((vec![index + 1], task));
+        for (mut position, subtask) in self::positions(&task.subtasks) {
+            position.insert(0, index + 1);
+            positions.push((position, subtask));
+        }
+    }
+    positions
+}
+
+/// Task at position in tasks, positions start at 1
+pub(crate) fn task_at_mut<'a>(tasks: &'a mut [Task], position: &[usize]) -> Option<&'a mut Task> {
+    let (first, rest) = position.split_first()?;
+    let task = tasks.get_mut(first.checked_sub(1)?)?;
+    match rest.is_empty() {
+        true => Some(task),
+        false => task_at_mut(&mut task.subtasks, rest),
+    }
+}
+
+/// Tasks the task at position is part of (its parent's subtasks or tasks) and its index in them
+pub(crate) fn siblings_mut<'a>(
+    tasks: &'a mut Vec<Task>,
+    position: &[usize],
+) -> Option<(&'a mut Vec<Task>, usize)> {
+    let (first, rest) = position.split_first()?;
+    let index = first.checked_sub(1)?;
+    if rest.is_empty() {
+        return (index < tasks.len()).then_some((tasks, index));
+    }
+    siblings_mut(&mut tasks.get_mut(index)?.subtasks, rest)
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Address::Position {
+                date,
+                section,
+                position,
+            } => {
+                let position: Vec<String> = position.iter().map(|p| p.to_string()).collect();
+                write!(
+                    f,
+                    "{}/{section}/{}",
+                    date.format("%Y-%m-%d"),
+                    position.join(".")
+                )
+            }
+            Address::Hash(hash) => write!(f, "{hash}"),
+        }
+    }
+}
+
+impl str::FromStr for Address {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        // section names may contain slashes, date and position can't
+        let Some((date, rest)) = s.split_once('/') else {
+            let is_hash = s.len() >= MIN_HASH_LEN && s.chars().all(|c| c.is_ascii_hexdigit());
+            return match is_hash {
+                true => Ok(Address::Hash(s.to_lowercase())),
+                false => err!("Invalid task address: {s}"),
+            };
+        };
+        let Some((section, position)) = rest.rsplit_once('/') else {
+            return err!("Invalid task address: {s}, expected date/section/position");
+        };
+        let Ok(date) = NaiveDate::parse_from_str(date, "%Y-%m-%d") else {
+            return err!("Invalid task address: {s}, invalid date {date}");
+        };
+        let position: Option<Vec<usize>> = position
+            .split('.')
+            .map(|p| p.parse().ok().filter(|p| *p > 0))
+            .collect();
+        let Some(position) = position else {
+            return err!("Invalid task address: {s}, positions are numbers starting at 1");
+        };
+        Ok(Address::Position {
+            date,
+            section: section.to_string(),
+            position,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use itertools::Itertools;
+
+    #[test]
+    fn parse_address() {
+        let address: Address = "2024-03-07/Work/3.1".parse().unwrap();
+        let expected = Address::Position {
+            date: NaiveDate::from_ymd_opt(2024, 3, 7).unwrap(),
+            section: "Work".to_string(),
+            position: vec![3, 1],
+        };
+        assert_eq!(address, expected);
+        assert_eq!(address.to_string(), "2024-03-07/Work/3.1");
+
+        let address: Address = "2024-03-07/Work/Ops/2".parse().unwrap();
+        assert!(matches!(address, Address::Position { section, .. } if section == "Work/Ops"));
+        let address: Address = "2024-03-07//1".parse().unwrap();
+        assert!(matches!(address, Address::Position { section, .. } if section.is_empty()));
+
+        let address: Address = "5F3A09c1".parse().unwrap();
+        assert_eq!(address, Address::Hash("5f3a09c1".to_string()));
+    }
+
+    #[test]
+    fn err_parse_address() {
+        for address in [
+            "deploy",
+            "abc",
+            "2024-03-07/1",
+            "2024-13-07/Work/1",
+            "2024-03-07/Work/0",
+            "2024-03-07/Work/x",
+        ] {
+            assert!(address.parse::<Address>().is_err(), "{address}");
+        }
+    }
+
+    #[test]
+    fn task_content_hash() {
+        let date = NaiveDate::from_ymd_opt(2024, 3, 7).unwrap();
+        let mut section = Section::new("Work");
+        section.tasks.push(Task::new("deploy"));
+        let hash = content_hash(date, &section, &[1]);
+        assert_eq!(hash.len(), HASH_LEN);
+
+        // status doesn't change the hash, text does
+        section.tasks[0].complete();
+        assert_eq!(content_hash(date, &section, &[1]), hash);
+        section.tasks[0].set_text("deploy again");
+        assert_ne!(content_hash(date, &section, &[1]), hash);
+    }
+
+    #[test]
+    fn duplicate_content_hash() {
+        let date = NaiveDate::from_ymd_opt(2024, 3, 7).unwrap();
+        let mut section = Section::new("Work");
+        for text in ["deploy", "review", "deploy"] {
+            let mut task = Task::new(text);
+            task.add_subtask(Task::new("build"));
+            section.tasks.push(task);
+        }
+
+        let hashes: Vec<String> = [vec![1], vec![3], vec![1, 1], vec![3, 1]]
+            .iter()
+            .map(|position| content_hash(date, &section, position))
+            .collect();
+        assert!(hashes.iter().all_unique(), "{hashes:?}");
+    }
+}
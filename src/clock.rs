@@ -0,0 +1,33 @@
+//! Where [crate::Todo] gets today's date from
+//!
+//! Todos use [SystemClock] unless given another clock with [crate::Todo::set_clock], e.g. a
+//! [FixedClock] to get the same results on every run.
+
+use crate::config::Config;
+use chrono::NaiveDate;
+use std::fmt;
+
+pub trait Clock: fmt::Debug + Send + Sync {
+    /// Date of the day that is going on, config has the timezone and day start hour
+    fn today(&self, config: &Config) -> NaiveDate;
+}
+
+/// Reads the date from the system clock
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+/// Always on the same date
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub struct FixedClock(pub NaiveDate);
+
+impl Clock for SystemClock {
+    fn today(&self, config: &Config) -> NaiveDate {
+        config.today()
+    }
+}
+
+impl Clock for FixedClock {
+    fn today(&self, _config: &Config) -> NaiveDate {
+        self.0
+    }
+}
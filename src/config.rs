@@ -0,0 +1,500 @@
+//! User settings
+//!
+//! Settings are read from `$XDG_CONFIG_HOME/netxt/config.toml` (`~/.config/netxt/config.toml` when
+//! `XDG_CONFIG_HOME` is not set), or from the file in `NETXT_CONFIG`. Every key is optional:
+//!
+//! ```toml
+//! file = "/home/me/notes/todo.txt"
+//! done-section = "Done"
+//! date-format = "[%Y-%m-%d]"
+//! day-start-hour = 4
+//! timezone = "Europe/Lisbon"
+//!
+//! [rollover]
+//! carry = "open"
+//! clear = ["Done"]
+//!
+//! [format]
+//! tab-width = 4
+//! blank-lines = 1
+//! ```
+//!
+//! Environment variables override the file, and command line flags override both, see [Overrides].
+//!
+//! `file` is only used when there's no `todo.txt` or `.netxt` in the current directory or any of its
+//! parents, see [Config::find_file].
+
+use crate::{
+    err,
+    rollover::{Carry, Rollover},
+    util::{Result, DEFAULT_DATE_FORMAT, DEFAULT_TODO_FILE},
+    Error,
+};
+use chrono::{
+    format::{Item, StrftimeItems},
+    DateTime, Duration, Local, NaiveDate, Utc,
+};
+use chrono_tz::Tz;
+use serde::Deserialize;
+use std::{env, fs::read_to_string, path::Path, path::PathBuf};
+
+#[derive(PartialEq, Eq, Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    /// Todo file used when none is given and no project todo file is found
+    pub file: PathBuf,
+    /// Section that starts empty every day when using [crate::Todo::next_day]
+    pub done_section: String,
+    /// Format of day headers, see [chrono::format::strftime]
+    pub date_format: String,
+    /// Hour new days start at, e.g. with 4 tasks added until 04:00 still go to the day before
+    pub day_start_hour: u32,
+    /// IANA name of the timezone days follow e.g. `Europe/Lisbon`, the system's when not set
+    pub timezone: Option<String>,
+    pub rollover: Rollover,
+    pub format: Format,
+    /// Settings from the environment and the command line, they also win over `.netxt.toml` files
+    #[serde(skip)]
+    pub overrides: Overrides,
+}
+
+/// Settings that override config files, each one can be set with a `NETXT_*` variable
+///
+/// | Setting | Variable |
+/// |---|---|
+/// | `done-section` | `NETXT_DONE_SECTION` |
+/// | `date-format` | `NETXT_DATE_FORMAT` |
+/// | `day-start-hour` | `NETXT_DAY_START_HOUR` |
+/// | `timezone` | `NETXT_TIMEZONE` |
+/// | `rollover.carry` | `NETXT_ROLLOVER_CARRY` |
+/// | `rollover.clear` | `NETXT_ROLLOVER_CLEAR`, comma separated |
+/// | `rollover.pinned` | `NETXT_ROLLOVER_PINNED`, comma separated |
+/// | `rollover.template` | `NETXT_ROLLOVER_TEMPLATE`, comma separated |
+/// | `format.tab-width` | `NETXT_TAB_WIDTH` |
+/// | `format.blank-lines` | `NETXT_BLANK_LINES` |
+#[derive(PartialEq, Eq, Debug, Clone, Default)]
+pub struct Overrides {
+    pub done_section: Option<String>,
+    pub date_format: Option<String>,
+    pub day_start_hour: Option<u32>,
+    pub timezone: Option<String>,
+    pub carry: Option<Carry>,
+    pub clear: Option<Vec<String>>,
+    pub pinned: Option<Vec<String>>,
+    pub template: Option<Vec<String>>,
+    pub tab_width: Option<usize>,
+    pub blank_lines: Option<usize>,
+}
+
+/// Names of project todo files, in the order they are looked for in a directory
+pub static TODO_FILE_NAMES: [&str; 2] = [DEFAULT_TODO_FILE, ".netxt"];
+
+/// How `netxt fmt` writes files
+#[derive(PartialEq, Eq, Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Format {
+    /// Columns a tab expands to
+    pub tab_width: usize,
+    /// Blank lines between sections and between days
+    pub blank_lines: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            file: DEFAULT_TODO_FILE.into(),
+            done_section: "Done".to_string(),
+            date_format: DEFAULT_DATE_FORMAT.to_string(),
+            day_start_hour: 0,
+            timezone: None,
+            rollover: Rollover::default(),
+            format: Format::default(),
+            overrides: Overrides::default(),
+        }
+    }
+}
+
+impl Default for Format {
+    fn default() -> Self {
+        Format {
+            tab_width: 4,
+            blank_lines: 1,
+        }
+    }
+}
+
+impl Config {
+    /// Reads the user's config file, if there's one, and applies `NETXT_*` environment variables
+    ///
+    /// path is used instead of the default config file when given, and must exist.
+    pub fn load(path: Option<&Path>) -> Result<Config> {
+        let path = path
+            .map(Path::to_path_buf)
+            .or_else(|| env::var_os("NETXT_CONFIG").map(PathBuf::from));
+        let config = match path {
+            Some(path) => Config::from_file(&path)?,
+            None => match Config::default_path() {
+                Some(path) if path.exists() => Config::from_file(&path)?,
+                _ => Config::default(),
+            },
+        };
+        config.with_env(|name| env::var(name).ok())
+    }
+
+    /// `$XDG_CONFIG_HOME/netxt/config.toml` or `$HOME/.config/netxt/config.toml`
+    pub fn default_path() -> Option<PathBuf> {
+        let config_home = env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+        Some(config_home.join("netxt").join("config.toml"))
+    }
+
+    pub fn from_file(path: &Path) -> Result<Config> {
+        let contents = read_to_string(path).map_err(|e| Error::io(path, e))?;
+        let config: Config = match toml::from_str(&contents) {
+            Ok(config) => config,
+            Err(e) => return err!("Invalid config {}: {}", path.display(), e.message()),
+        };
+        config.validate()
+    }
+
+    /// Overrides settings with the `NETXT_*` variables var returns
+    pub fn with_env(self, var: impl Fn(&str) -> Option<String>) -> Result<Config> {
+        self.with_overrides(Overrides::from_env(var)?)
+    }
+
+    /// Overrides settings with the ones set in overrides, which win over earlier overrides
+    pub fn with_overrides(mut self, overrides: Overrides) -> Result<Config> {
+        self.overrides = self.overrides.merge(overrides);
+        let overrides = &self.overrides;
+        if let Some(name) = &overrides.done_section {
+            self.done_section = name.clone();
+        }
+        if let Some(format) = &overrides.date_format {
+            self.date_format = format.clone();
+        }
+        if let Some(hour) = overrides.day_start_hour {
+            self.day_start_hour = hour;
+        }
+        if let Some(timezone) = &overrides.timezone {
+            self.timezone = Some(timezone.clone());
+        }
+        if let Some(width) = overrides.tab_width {
+            self.format.tab_width = width;
+        }
+        if let Some(lines) = overrides.blank_lines {
+            self.format.blank_lines = lines;
+        }
+        self.set_rollover(self.rollover.clone());
+        self.validate()
+    }
+
+    /// Replaces the rollover policy, keeping the parts of it that are overridden
+    pub fn set_rollover(&mut self, mut rollover: Rollover) {
+        let overrides = &self.overrides;
+        if let Some(carry) = overrides.carry {
+            rollover.carry = carry;
+        }
+        if let Some(clear) = &overrides.clear {
+            rollover.clear = clear.clone();
+        }
+        if let Some(pinned) = &overrides.pinned {
+            rollover.pinned = pinned.clone();
+        }
+        if let Some(template) = &overrides.template {
+            rollover.template = template.clone();
+        }
+        self.rollover = rollover;
+    }
+
+    /// Date of the day that is going on now
+    pub fn today(&self) -> NaiveDate {
+        self.date_at(Utc::now())
+    }
+
+    /// Date of the day that is going on at time, following timezone and day_start_hour
+    pub fn date_at(&self, time: DateTime<Utc>) -> NaiveDate {
+        // timezone is checked by validate, an invalid one falls back to the system's
+        let local = match self.timezone.as_ref().and_then(|tz| tz.parse::<Tz>().ok()) {
+            Some(tz) => time.with_timezone(&tz).naive_local(),
+            None => time.with_timezone(&Local).naive_local(),
+        };
+        (local - Duration::hours(self.day_start_hour.into())).date()
+    }
+
+    /// Closest project todo file, looking in dir and then in its parents, or the configured file
+    pub fn find_file(&self, dir: &Path) -> PathBuf {
+        for dir in dir.ancestors() {
+            for name in TODO_FILE_NAMES {
+                let path = dir.join(name);
+                if path.is_file() {
+                    return path;
+                }
+            }
+        }
+        self.file.clone()
+    }
+
+    /// Checks that settings can be used, e.g. that dates written in date_format can be read back
+    pub fn validate(self) -> Result<Config> {
+        let format = &self.date_format;
+        if StrftimeItems::new(format).any(|item| item == Item::Error) {
+            return err!("Invalid date format: {format}");
+        }
+        let date = NaiveDate::from_ymd_opt(2024, 12, 31).unwrap_or_default();
+        let header = date.format(format).to_string();
+        if NaiveDate::parse_from_str(&header, format) != Ok(date) {
+            return err!("Invalid date format: {format}, dates written with it can't be read back");
+        }
+        if header.trim_start().starts_with('-') || header.trim() != header {
+            return err!(
+                "Invalid date format: {format}, headers can't start with a dash or spaces"
+            );
+        }
+        if self.day_start_hour > 23 {
+            return err!(
+                "Invalid day start hour: {}, must be 0 to 23",
+                self.day_start_hour
+            );
+        }
+        if let Some(timezone) = &self.timezone {
+            if timezone.parse::<Tz>().is_err() {
+                return err!("Invalid timezone: {timezone}, expected a name like Europe/Lisbon");
+            }
+        }
+        if self.format.tab_width == 0 {
+            return err!("Invalid format: tab-width must be at least 1");
+        }
+        Ok(self)
+    }
+}
+
+impl Overrides {
+    /// Settings in the `NETXT_*` variables var returns
+    pub fn from_env(var: impl Fn(&str) -> Option<String>) -> Result<Overrides> {
+        let list = |name| {
+            var(name).map(|value: String| {
+                value
+                    .split(',')
+                    .map(|item| item.trim().to_string())
+                    .filter(|item| !item.is_empty())
+                    .collect()
+            })
+        };
+        Ok(Overrides {
+            done_section: var("NETXT_DONE_SECTION"),
+            date_format: var("NETXT_DATE_FORMAT"),
+            day_start_hour: parse_var("NETXT_DAY_START_HOUR", var("NETXT_DAY_START_HOUR"))?,
+            timezone: var("NETXT_TIMEZONE"),
+            carry: parse_var("NETXT_ROLLOVER_CARRY", var("NETXT_ROLLOVER_CARRY"))?,
+            clear: list("NETXT_ROLLOVER_CLEAR"),
+            pinned: list("NETXT_ROLLOVER_PINNED"),
+            template: list("NETXT_ROLLOVER_TEMPLATE"),
+            tab_width: parse_var("NETXT_TAB_WIDTH", var("NETXT_TAB_WIDTH"))?,
+            blank_lines: parse_var("NETXT_BLANK_LINES", var("NETXT_BLANK_LINES"))?,
+        })
+    }
+
+    /// Settings of self, replaced by the ones set in other
+    pub fn merge(self, other: Overrides) -> Overrides {
+        Overrides {
+            done_section: other.done_section.or(self.done_section),
+            date_format: other.date_format.or(self.date_format),
+            day_start_hour: other.day_start_hour.or(self.day_start_hour),
+            timezone: other.timezone.or(self.timezone),
+            carry: other.carry.or(self.carry),
+            clear: other.clear.or(self.clear),
+            pinned: other.pinned.or(self.pinned),
+            template: other.template.or(self.template),
+            tab_width: other.tab_width.or(self.tab_width),
+            blank_lines: other.blank_lines.or(self.blank_lines),
+        }
+    }
+}
+
+fn parse_var<T: std::str::FromStr>(name: &str, value: Option<String>) -> Result<Option<T>> {
+    match value {
+        None => Ok(None),
+        Some(value) => match value.trim().parse() {
+            Ok(parsed) => Ok(Some(parsed)),
+            Err(_) => err!("Invalid {name}: {value}"),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::rollover::Carry;
+    use indoc::indoc;
+    use std::{collections::HashMap, fs};
+    use tempfile::tempdir;
+
+    #[test]
+    fn read_config() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        let contents = indoc! {r#"
+            file = "/home/me/todo.txt"
+            date-format = "%d/%m/%Y"
+
+            [rollover]
+            carry = "open"
+
+            [format]
+            blank-lines = 2
+        "#};
+        fs::write(&path, contents).unwrap();
+
+        let expected = Config {
+            file: "/home/me/todo.txt".into(),
+            date_format: "%d/%m/%Y".to_string(),
+            rollover: Rollover {
+                carry: Carry::Open,
+                ..Default::default()
+            },
+            format: Format {
+                blank_lines: 2,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert_eq!(Config::from_file(&path).unwrap(), expected);
+        assert_eq!(Config::load(Some(&path)).unwrap().file, expected.file);
+    }
+
+    #[test]
+    fn env_overrides_config() {
+        let vars = HashMap::from([
+            ("NETXT_FILE", "work.txt"),
+            ("NETXT_DONE_SECTION", "Finished"),
+        ]);
+        let config = Config::default()
+            .with_env(|name| vars.get(name).map(|value| value.to_string()))
+            .unwrap();
+        // NETXT_FILE is looked at before project todo files, not here
+        assert_eq!(config.file, PathBuf::from(DEFAULT_TODO_FILE));
+        assert_eq!(config.done_section, "Finished");
+        assert_eq!(config.date_format, DEFAULT_DATE_FORMAT);
+    }
+
+    #[test]
+    fn env_overrides_rollover_and_format() {
+        let vars = HashMap::from([
+            ("NETXT_DAY_START_HOUR", "4"),
+            ("NETXT_TIMEZONE", "Europe/Lisbon"),
+            ("NETXT_ROLLOVER_CARRY", "not-done"),
+            ("NETXT_ROLLOVER_CLEAR", "Done, Inbox"),
+            ("NETXT_BLANK_LINES", "0"),
+        ]);
+        let config = Config::default()
+            .with_env(|name| vars.get(name).map(|value| value.to_string()))
+            .unwrap();
+        assert_eq!(config.day_start_hour, 4);
+        assert_eq!(config.timezone.as_deref(), Some("Europe/Lisbon"));
+        assert_eq!(config.rollover.carry, Carry::NotDone);
+        assert_eq!(config.rollover.clear, vec!["Done", "Inbox"]);
+        assert_eq!(config.format.blank_lines, 0);
+        assert_eq!(config.format.tab_width, 4);
+
+        // flags win over the environment, and both win over a policy set later
+        let flags = Overrides {
+            carry: Some(Carry::Open),
+            ..Default::default()
+        };
+        let mut config = config.with_overrides(flags).unwrap();
+        config.set_rollover(Rollover {
+            pinned: vec!["Ideas".to_string()],
+            ..Default::default()
+        });
+        assert_eq!(config.rollover.carry, Carry::Open);
+        assert_eq!(config.rollover.clear, vec!["Done", "Inbox"]);
+        assert_eq!(config.rollover.pinned, vec!["Ideas"]);
+
+        for (name, value) in [
+            ("NETXT_DAY_START_HOUR", "24"),
+            ("NETXT_DAY_START_HOUR", "four"),
+            ("NETXT_ROLLOVER_CARRY", "some"),
+            ("NETXT_TIMEZONE", "Mars/Olympus"),
+        ] {
+            let config = Config::default().with_env(|var| (var == name).then(|| value.to_string()));
+            assert!(config.is_err(), "{name}={value}");
+        }
+    }
+
+    #[test]
+    fn date_with_day_start_and_timezone() {
+        let time = |s: &str| s.parse::<DateTime<Utc>>().unwrap();
+        let date = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+        let mut config = Config {
+            timezone: Some("UTC".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            config.date_at(time("2024-03-07T01:30:00Z")),
+            date(2024, 3, 7)
+        );
+
+        config.day_start_hour = 4;
+        assert_eq!(
+            config.date_at(time("2024-03-07T01:30:00Z")),
+            date(2024, 3, 6)
+        );
+        assert_eq!(
+            config.date_at(time("2024-03-07T04:00:00Z")),
+            date(2024, 3, 7)
+        );
+
+        // 01:30 in Tokyo is still the 6th in UTC
+        config.timezone = Some("Asia/Tokyo".to_string());
+        config.day_start_hour = 0;
+        assert_eq!(
+            config.date_at(time("2024-03-06T16:30:00Z")),
+            date(2024, 3, 7)
+        );
+    }
+
+    #[test]
+    fn find_project_file() {
+        let dir = tempdir().unwrap();
+        let subdir = dir.path().join("src").join("bin");
+        fs::create_dir_all(&subdir).unwrap();
+        let config = Config {
+            file: "/home/me/todo.txt".into(),
+            ..Default::default()
+        };
+        assert_eq!(config.find_file(&subdir), config.file);
+
+        fs::write(dir.path().join(".netxt"), "").unwrap();
+        assert_eq!(config.find_file(&subdir), dir.path().join(".netxt"));
+
+        // todo.txt wins in the same directory, a closer file wins over both
+        fs::write(dir.path().join("todo.txt"), "").unwrap();
+        assert_eq!(config.find_file(&subdir), dir.path().join("todo.txt"));
+        fs::write(dir.path().join("src").join(".netxt"), "").unwrap();
+        assert_eq!(
+            config.find_file(&subdir),
+            dir.path().join("src").join(".netxt")
+        );
+    }
+
+    #[test]
+    fn err_invalid_config() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        for contents in [
+            "unknown = 1\n",
+            "date-format = \"%Q\"\n",
+            "date-format = \"%Y\"\n",
+            "[format]\ntab-width = 0\n",
+            "day-start-hour = 24\n",
+            "timezone = \"Mars/Olympus\"\n",
+        ] {
+            fs::write(&path, contents).unwrap();
+            assert!(Config::from_file(&path).is_err(), "{contents}");
+        }
+        assert!(Config::from_file(&dir.path().join("missing.toml")).is_err());
+    }
+}
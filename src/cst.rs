@@ -0,0 +1,772 @@
+//! Lossless syntax tree of a todo file
+//!
+//! Every line of the source belongs to exactly one node, so days, sections and tasks that were
+//! not modified can be written back exactly as they were read, blank lines and all.
+
+use crate::{
+    day::{next_day_lines, Day},
+    error::{Error, Location},
+    section::{next_section_lines, Section},
+    task::{Child, Note, Task},
+    util::{Result, DEFAULT_DATE_FORMAT},
+};
+use chrono::NaiveDate;
+use std::{collections::HashMap, iter::Peekable, ops::Range};
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct SyntaxTree {
+    /// Source lines, including line endings
+    lines: Vec<String>,
+    /// Lines before the first day
+    pub preamble: Range<usize>,
+    pub days: Vec<DayNode>,
+    /// Format of day headers, used for days that are added
+    date_format: String,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct DayNode {
+    /// Day as it was parsed from the source
+    pub day: Day,
+    /// Date line and blank lines after it
+    pub head: Range<usize>,
+    pub sections: Vec<SectionNode>,
+    /// Blank lines at the end of the day, and lines of days after it that could not be parsed
+    pub trailing: Range<usize>,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct SectionNode {
+    /// Section as it was parsed from the source
+    pub section: Section,
+    /// Section name line and blank lines after it, empty for anonymous sections
+    pub head: Range<usize>,
+    pub tasks: Vec<TaskNode>,
+    /// Blank lines between this section and the next one
+    pub trailing: Range<usize>,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct TaskNode {
+    /// Task as it was parsed from the source, with its notes and subtasks
+    pub task: Task,
+    /// Task lines and blank or invalid lines between this task and the next one
+    pub lines: Range<usize>,
+    /// Task line and the blank or invalid lines after it
+    pub head: Range<usize>,
+    /// Each note with its line and the blank or invalid lines after it, in the order they are written
+    pub notes: Vec<(Note, Range<usize>)>,
+    pub subtasks: Vec<TaskNode>,
+}
+
+impl DayNode {
+    pub fn lines(&self) -> Range<usize> {
+        self.head.start..self.trailing.end
+    }
+}
+
+impl SectionNode {
+    pub fn lines(&self) -> Range<usize> {
+        self.head.start..self.trailing.end
+    }
+}
+
+impl SyntaxTree {
+    pub fn parse(source: &str) -> Result<SyntaxTree> {
+        SyntaxTree::parse_with(source, DEFAULT_DATE_FORMAT)
+    }
+
+    /// Parses a source whose day headers are written in date_format
+    pub fn parse_with(source: &str, date_format: &str) -> Result<SyntaxTree> {
+        let (tree, errors) = SyntaxTree::parse_lenient_with(source, date_format);
+        match errors.into_iter().next() {
+            Some(e) => Err(e),
+            None => Ok(tree),
+        }
+    }
+
+    /// Parses as much of the source as possible and returns every error found, sorted by line
+    ///
+    /// Lines that can't be parsed are kept in the tree as trivia so they are written back as they are.
+    /// Days with an invalid date are skipped entirely.
+    pub fn parse_lenient(source: &str) -> (SyntaxTree, Vec<Error>) {
+        SyntaxTree::parse_lenient_with(source, DEFAULT_DATE_FORMAT)
+    }
+
+    /// Same as [SyntaxTree::parse_lenient] for a source whose day headers are written in date_format
+    pub fn parse_lenient_with(source: &str, date_format: &str) -> (SyntaxTree, Vec<Error>) {
+        let lines: Vec<String> = source.split_inclusive('\n').map(String::from).collect();
+        let text: Vec<&str> = lines.iter().map(|line| strip_line_ending(line)).collect();
+        let mut trivia: Vec<bool> = text.iter().map(|line| line.trim().is_empty()).collect();
+
+        let mut days: Vec<DayNode> = Vec::new();
+        let mut errors = Vec::new();
+        let mut index = 0;
+        while let Some(day_lines) = next_day_lines(&text, index, date_format) {
+            index = day_lines.end;
+            match parse_day(&text, &mut trivia, day_lines.clone(), date_format) {
+                Ok((day, day_errors)) => {
+                    days.push(day);
+                    errors.extend(day_errors);
+                }
+                Err(e) => {
+                    // keep lines of the skipped day with the day before it
+                    if let Some(previous) = days.last_mut() {
+                        previous.trailing.end = day_lines.end;
+                    }
+                    errors.push(e);
+                }
+            }
+        }
+
+        // every task must be inside a day
+        let preamble = 0..days.first().map_or(lines.len(), |day| day.head.start);
+        for index in preamble.clone() {
+            if text[index].trim_start().starts_with('-') {
+                errors.push(Error::OrphanTask {
+                    text: text[index].trim().to_string(),
+                    location: Location::of_line(index, text[index]),
+                });
+            }
+        }
+
+        errors.sort_by_key(|e| e.location().map(|location| location.line));
+        let tree = SyntaxTree {
+            lines,
+            preamble,
+            days,
+            date_format: date_format.to_string(),
+        };
+        (tree, errors)
+    }
+
+    /// Source text of a range of lines
+    pub fn text(&self, lines: Range<usize>) -> String {
+        self.lines[lines].concat()
+    }
+
+    /// Whole source text
+    pub fn source(&self) -> String {
+        self.lines.concat()
+    }
+
+    /// Source line without its line ending
+    pub fn line(&self, index: usize) -> &str {
+        strip_line_ending(&self.lines[index])
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.lines.len()
+    }
+
+    /// Days that are part of the todo, when a date appears more than once the last one wins
+    pub fn days(&self) -> HashMap<NaiveDate, Day> {
+        self.days
+            .iter()
+            .map(|node| (node.day.date, node.day.clone()))
+            .collect()
+    }
+
+    /// Writes days back to text, reusing the source lines of everything that did not change
+    pub fn render(&self, days: &HashMap<NaiveDate, Day>) -> String {
+        let mut out = self.text(self.preamble.clone());
+
+        // a date may appear more than once, only the last one is part of the todo
+        let owners: HashMap<NaiveDate, usize> = self
+            .days
+            .iter()
+            .enumerate()
+            .map(|(i, node)| (node.day.date, i))
+            .collect();
+
+        // new days are placed following the order of the file, newest first unless the file is oldest first
+        let ascending = match (self.days.first(), self.days.last()) {
+            (Some(first), Some(last)) => first.day.date < last.day.date,
+            _ => false,
+        };
+        let comes_before = |new: NaiveDate, old: NaiveDate| {
+            if ascending {
+                new < old
+            } else {
+                new > old
+            }
+        };
+        let mut new_days: Vec<&Day> = days
+            .values()
+            .filter(|day| !owners.contains_key(&day.date))
+            .collect();
+        new_days.sort_by(|a, b| match comes_before(a.date, b.date) {
+            true => std::cmp::Ordering::Less,
+            false => std::cmp::Ordering::Greater,
+        });
+        let mut new_days = new_days.into_iter().peekable();
+
+        for (i, node) in self.days.iter().enumerate() {
+            while let Some(day) = new_days.next_if(|day| comes_before(day.date, node.day.date)) {
+                push_generated(
+                    &mut out,
+                    &format!("{}\n\n", generated_day(day, &self.date_format)),
+                );
+            }
+
+            if owners[&node.day.date] != i {
+                // days overwritten by a later day with the same date are kept as they are
+                out.push_str(&self.text(node.lines()));
+                continue;
+            }
+            match days.get(&node.day.date) {
+                None => {} // day was removed
+                Some(day) if *day == node.day => out.push_str(&self.text(node.lines())),
+                Some(day) => self.render_day(&mut out, day, node),
+            }
+        }
+
+        for day in new_days {
+            if !out.is_empty() && !out.ends_with("\n\n") {
+                push_generated(&mut out, "\n");
+            }
+            push_generated(
+                &mut out,
+                &format!("{}\n", generated_day(day, &self.date_format)),
+            );
+        }
+        out
+    }
+
+    fn render_day(&self, out: &mut String, day: &Day, node: &DayNode) {
+        match day.planned == node.day.planned {
+            true => out.push_str(&self.text(node.head.clone())),
+            // header is the first line of head
+            false => {
+                push_generated(out, &format!("{}\n", day.header(&self.date_format)));
+                out.push_str(&self.text(node.head.start + 1..node.head.end));
+            }
+        }
+
+        let mut used = vec![false; node.sections.len()];
+        let mut previous: Option<bool> = None; // whether previous section was generated
+        for section in &day.sections {
+            let found = (0..node.sections.len())
+                .find(|&i| !used[i] && node.sections[i].section.name == section.name);
+            // keep generated sections apart from their neighbours
+            let separate = match found {
+                Some(i) => {
+                    used[i] = true;
+                    previous == Some(true)
+                }
+                None => previous.is_some(),
+            };
+            if separate && !out.ends_with("\n\n") {
+                push_generated(out, "\n");
+            }
+
+            match found {
+                Some(i) if node.sections[i].section == *section => {
+                    out.push_str(&self.text(node.sections[i].lines()))
+                }
+                Some(i) => self.render_section(out, section, &node.sections[i]),
+                None => push_generated(out, &format!("{}\n", generated_section(section))),
+            }
+            previous = Some(found.is_none());
+        }
+
+        out.push_str(&self.text(node.trailing.clone()));
+    }
+
+    fn render_section(&self, out: &mut String, section: &Section, node: &SectionNode) {
+        out.push_str(&self.text(node.head.clone()));
+
+        let found = match_tasks(&section.tasks, &node.tasks);
+        for (task, found) in section.tasks.iter().zip(found) {
+            match found {
+                Some(i) => self.render_task(out, task, &node.tasks[i]),
+                None => push_generated(out, &format!("{task}\n")),
+            }
+        }
+
+        out.push_str(&self.text(node.trailing.clone()));
+    }
+
+    /// Writes task reusing the source lines of its line, notes and subtasks that did not change
+    fn render_task(&self, out: &mut String, task: &Task, node: &TaskNode) {
+        if *task == node.task {
+            out.push_str(&self.text(node.lines.clone()));
+            return;
+        }
+        match task.indent == node.task.indent && task.line() == node.task.line() {
+            true => out.push_str(&self.text(node.head.clone())),
+            false => {
+                push_generated(out, &format!("{}{}\n", task.indent, task.line()));
+                out.push_str(&self.text(node.head.start + 1..node.head.end));
+            }
+        }
+
+        let subtasks = match_tasks(&task.subtasks, &node.subtasks);
+        let mut used = vec![false; node.notes.len()];
+        let mut index = 0;
+        for child in task.children() {
+            match child {
+                Child::Note(note) => {
+                    // notes move when subtasks are added before them, their position doesn't matter
+                    let found = (0..node.notes.len()).find(|&i| {
+                        let (old, _) = &node.notes[i];
+                        !used[i] && old.text == note.text && old.indent == note.indent
+                    });
+                    match found {
+                        Some(i) => {
+                            used[i] = true;
+                            out.push_str(&self.text(node.notes[i].1.clone()));
+                        }
+                        None => push_generated(out, &format!("{}\n", task.note_line(note))),
+                    }
+                }
+                Child::Subtask(subtask) => {
+                    match subtasks[index] {
+                        Some(i) => self.render_task(out, subtask, &node.subtasks[i]),
+                        None => push_generated(out, &format!("{subtask}\n")),
+                    }
+                    index += 1;
+                }
+            }
+        }
+    }
+}
+
+/// Node each task was parsed as, tasks that didn't change first, then tasks with the same text,
+/// then tasks in the same place
+fn match_tasks(tasks: &[Task], nodes: &[TaskNode]) -> Vec<Option<usize>> {
+    let mut found: Vec<Option<usize>> = vec![None; tasks.len()];
+    let mut used = vec![false; nodes.len()];
+    let passes: [&dyn Fn(usize, usize) -> bool; 3] = [
+        &|t, n| nodes[n].task == tasks[t],
+        &|t, n| nodes[n].task.text == tasks[t].text,
+        &|t, n| t == n,
+    ];
+    for matches in passes {
+        for (t, found) in found.iter_mut().enumerate() {
+            if found.is_none() {
+                *found = (0..nodes.len()).find(|&n| !used[n] && matches(t, n));
+                if let Some(n) = *found {
+                    used[n] = true;
+                }
+            }
+        }
+    }
+    found
+}
+
+/// Parses the day in lines, lines with errors are marked as trivia
+fn parse_day(
+    text: &[&str],
+    trivia: &mut [bool],
+    lines: Range<usize>,
+    date_format: &str,
+) -> Result<(DayNode, Vec<Error>)> {
+    let (day, errors) = Day::parse_lenient(&text[lines.clone()].join("\n"), date_format)
+        .map_err(|e: Error| e.offset_lines(lines.start))?;
+    let errors: Vec<Error> = errors
+        .into_iter()
+        .map(|e| e.offset_lines(lines.start))
+        .collect();
+    for location in errors.iter().filter_map(|e| e.location()) {
+        trivia[location.line - 1] = true;
+    }
+    let content_end = skip_trivia_back(trivia, lines.start + 1, lines.end);
+
+    // same boundaries Day uses to split sections
+    let mut starts = Vec::new();
+    let mut index = lines.start + 1;
+    while let Some(section_lines) = next_section_lines(&text[..content_end], index) {
+        index = section_lines.end;
+        starts.push(section_lines.start);
+    }
+
+    let mut sections = Vec::new();
+    for (i, (section, &start)) in day.sections.iter().zip(&starts).enumerate() {
+        let end = starts.get(i + 1).copied().unwrap_or(content_end);
+        sections.push(parse_section(text, trivia, section, start..end));
+    }
+
+    let head_end = starts.first().copied().unwrap_or(content_end);
+    let node = DayNode {
+        day,
+        head: lines.start..head_end,
+        sections,
+        trailing: content_end..lines.end,
+    };
+    Ok((node, errors))
+}
+
+fn parse_section(
+    text: &[&str],
+    trivia: &[bool],
+    section: &Section,
+    lines: Range<usize>,
+) -> SectionNode {
+    let content_end = skip_trivia_back(trivia, lines.start, lines.end);
+
+    // anonymous sections start with their first task
+    let mut index = lines.start;
+    if !text[index].trim_start().starts_with('-') {
+        index = skip_trivia(trivia, index + 1, content_end);
+    }
+    let head = lines.start..index;
+
+    // each task takes one line for itself and each of its notes and subtasks
+    let mut starts = Vec::new();
+    for task in &section.tasks {
+        task_starts(trivia, task, &mut index, content_end, &mut starts);
+    }
+    let mut starts = starts.into_iter().peekable();
+    let tasks = section
+        .tasks
+        .iter()
+        .map(|task| task_node(task, &mut starts, content_end))
+        .collect();
+
+    SectionNode {
+        section: section.clone(),
+        head,
+        tasks,
+        trailing: content_end..lines.end,
+    }
+}
+
+/// Pushes the first line of task and of each of its notes and subtasks, in the order they are written
+fn task_starts(
+    trivia: &[bool],
+    task: &Task,
+    index: &mut usize,
+    end: usize,
+    starts: &mut Vec<usize>,
+) {
+    *index = skip_trivia(trivia, *index, end);
+    starts.push(*index);
+    *index += 1;
+    // blank notes are the blank lines right above the next note
+    let mut blank_lines = 0;
+    for child in task.children() {
+        match child {
+            Child::Note(note) if note.text.is_empty() => blank_lines += 1,
+            Child::Note(_) => {
+                let line = skip_trivia(trivia, *index, end);
+                starts.extend(line - blank_lines..=line);
+                blank_lines = 0;
+                *index = line + 1;
+            }
+            Child::Subtask(subtask) => task_starts(trivia, subtask, index, end, starts),
+        }
+    }
+}
+
+/// Node of task, each line in starts runs until the next one
+fn task_node(
+    task: &Task,
+    starts: &mut Peekable<impl Iterator<Item = usize>>,
+    end: usize,
+) -> TaskNode {
+    let head = next_lines(starts, end);
+    let mut notes = Vec::new();
+    let mut subtasks = Vec::new();
+    for child in task.children() {
+        match child {
+            Child::Note(note) => notes.push((note.clone(), next_lines(starts, end))),
+            Child::Subtask(subtask) => subtasks.push(task_node(subtask, starts, end)),
+        }
+    }
+    TaskNode {
+        task: task.clone(),
+        lines: head.start..starts.peek().copied().unwrap_or(end),
+        head,
+        notes,
+        subtasks,
+    }
+}
+
+/// Lines from the next start up to the one after it
+fn next_lines(starts: &mut Peekable<impl Iterator<Item = usize>>, end: usize) -> Range<usize> {
+    let start = starts.next().unwrap_or(end);
+    start..starts.peek().copied().unwrap_or(end)
+}
+
+/// First line at or after index that is not blank or an error
+fn skip_trivia(trivia: &[bool], mut index: usize, end: usize) -> usize {
+    while index < end && trivia[index] {
+        index += 1;
+    }
+    index
+}
+
+/// End of the lines before end that are followed only by trivia, never before start
+fn skip_trivia_back(trivia: &[bool], start: usize, mut end: usize) -> usize {
+    while end > start && trivia[end - 1] {
+        end -= 1;
+    }
+    end
+}
+
+fn strip_line_ending(line: &str) -> &str {
+    let line = line.strip_suffix('\n').unwrap_or(line);
+    line.strip_suffix('\r').unwrap_or(line)
+}
+
+/// Appends generated text, making sure it starts on a new line
+fn push_generated(out: &mut String, text: &str) {
+    if !out.is_empty() && !out.ends_with('\n') {
+        out.push('\n');
+    }
+    out.push_str(text);
+}
+
+fn generated_day(day: &Day, date_format: &str) -> String {
+    day.render(date_format).trim_end().to_string()
+}
+
+fn generated_section(section: &Section) -> String {
+    // anonymous sections have no name line
+    section.to_string().trim_matches('\n').to_string()
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use indoc::indoc;
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn unchanged_roundtrip() {
+        let source = indoc! {"
+            Notes before the first day are kept
+
+            [2024-03-07]
+            Work
+            - [x] deploy
+              cargo build --release
+
+
+            -   review PR
+            Home
+            - water plants
+
+            [2024-03-06]
+
+            - anonymous task
+        "};
+
+        let tree = SyntaxTree::parse(source).expect("Unable to parse tree");
+        assert_eq!(tree.days.len(), 2);
+        assert_eq!(
+            tree.text(tree.preamble.clone()),
+            "Notes before the first day are kept\n\n"
+        );
+        assert_eq!(tree.render(&tree.days()), source);
+    }
+
+    #[test]
+    fn err_orphan_task() {
+        let source = indoc! {"
+            Some notes
+            - task without a day
+            [2024-03-07]
+            - task
+        "};
+
+        match SyntaxTree::parse(source) {
+            Err(Error::OrphanTask { location, .. }) => assert_eq!(location.line, 2),
+            other => panic!("Expected orphan task error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn err_location_in_file() {
+        let source = indoc! {"
+            [2024-03-07]
+            - task
+
+            [2024-03-06]
+            Work
+              note without a task
+        "};
+
+        match SyntaxTree::parse(source) {
+            Err(Error::InvalidTask { location, .. }) => {
+                assert_eq!((location.line, location.column), (6, 3))
+            }
+            other => panic!("Expected invalid task error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn parse_lenient_keeps_invalid_lines() {
+        let source = indoc! {"
+            - orphan
+            [2024-03-08]
+            Work
+              stray note
+            - task 1
+            - task 2
+
+            [2024-13-01]
+            - lost task
+
+            [2024-03-06]
+            - task 3
+        "};
+
+        let (tree, errors) = SyntaxTree::parse_lenient(source);
+        let lines: Vec<usize> = errors
+            .iter()
+            .filter_map(|e| e.location())
+            .map(|location| location.line)
+            .collect();
+        assert_eq!(lines, vec![1, 4, 8]);
+
+        let dates: Vec<NaiveDate> = tree.days.iter().map(|node| node.day.date).collect();
+        assert_eq!(dates, vec![date("2024-03-08"), date("2024-03-06")]);
+        assert_eq!(
+            tree.days[0].day.sections[0].tasks,
+            vec![Task::new("task 1"), Task::new("task 2")]
+        );
+
+        // invalid lines are written back, even when the day around them changes
+        let mut days = tree.days();
+        days.get_mut(&date("2024-03-08")).unwrap().sections[0]
+            .tasks
+            .push(Task::new("task 4"));
+        let expected = source.replace("- task 2\n", "- task 2\n- task 4\n");
+        assert_eq!(tree.render(&days), expected);
+    }
+
+    #[test]
+    fn only_edited_lines_change() {
+        let source = indoc! {"
+            [2024-03-07]
+            Work
+            - deploy
+            -   review PR
+
+            Home
+            - water plants
+
+
+            [2024-03-06]
+            Work
+            -   review PR
+        "};
+        let tree = SyntaxTree::parse(source).expect("Unable to parse tree");
+
+        let mut days = tree.days();
+        let day = days.get_mut(&date("2024-03-07")).unwrap();
+        day.sections[0].tasks[0].complete();
+        day.sections[1].tasks.push(Task::new("buy milk"));
+
+        let expected = indoc! {"
+            [2024-03-07]
+            Work
+            - [x] deploy
+            -   review PR
+
+            Home
+            - water plants
+            - buy milk
+
+
+            [2024-03-06]
+            Work
+            -   review PR
+        "};
+        assert_eq!(tree.render(&days), expected);
+    }
+
+    #[test]
+    fn only_edited_subtask_changes() {
+        let source = indoc! {"
+            [2024-03-07]
+            Work
+            - release
+                notes written deeper
+                    with code
+
+                and a second paragraph
+              -   build
+                  cargo build
+              - [ ]   test
+              -   publish
+                after publishing
+            -  review
+        "};
+        let tree = SyntaxTree::parse(source).expect("Unable to parse tree");
+
+        let mut days = tree.days();
+        let release = &mut days.get_mut(&date("2024-03-07")).unwrap().sections[0].tasks[0];
+        release.subtasks[1].complete();
+        release.add_subtask(Task::new("announce"));
+
+        let expected = source
+            .replace("- [ ]   test", "- [x] test")
+            .replace("  after publishing\n", "  after publishing\n  - announce\n");
+        assert_eq!(tree.render(&days), expected);
+    }
+
+    #[test]
+    fn new_days_and_sections() {
+        let source = indoc! {"
+            [2024-03-06]
+            Work
+            - deploy
+        "};
+        let tree = SyntaxTree::parse(source).expect("Unable to parse tree");
+
+        let mut days = tree.days();
+        days.get_mut(&date("2024-03-06"))
+            .unwrap()
+            .sections
+            .push(Section::new("Done"));
+        let mut new_day = Day::new(date("2024-03-07"));
+        let mut section = Section::new("Work");
+        section.tasks.push(Task::new("review PR"));
+        new_day.sections.push(section);
+        days.insert(new_day.date, new_day);
+
+        let expected = indoc! {"
+            [2024-03-07]
+            Work
+            - review PR
+
+            [2024-03-06]
+            Work
+            - deploy
+
+            Done
+        "};
+        assert_eq!(tree.render(&days), expected);
+    }
+
+    #[test]
+    fn removed_tasks() {
+        let source = indoc! {"
+            [2024-03-06]
+            Work
+            - deploy
+              with notes
+            - review PR
+        "};
+        let tree = SyntaxTree::parse(source).expect("Unable to parse tree");
+
+        let mut days = tree.days();
+        days.get_mut(&date("2024-03-06")).unwrap().sections[0]
+            .tasks
+            .remove(0);
+
+        let expected = indoc! {"
+            [2024-03-06]
+            Work
+            - review PR
+        "};
+        assert_eq!(tree.render(&days), expected);
+    }
+}
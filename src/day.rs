@@ -1,21 +1,81 @@
 use crate::{
     err,
-    section::{Section, SectionIterator},
+    error::{Error, Location},
+    section::{next_section_lines, Section},
+    task::Status,
     util::*,
 };
 use chrono::NaiveDate;
 use itertools::Itertools;
-use std::{cmp::Ordering, error, fmt, str};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::{cmp::Ordering, collections::HashMap, fmt, ops::Range, str};
+
+/// Written after the date of days that were planned ahead e.g. `[2024-12-31] (planned)`
+pub static PLANNED_MARKER: &str = "(planned)";
 
 #[derive(PartialEq, Eq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Day {
     pub date: NaiveDate,
+    /// Day was created before its date, it gets merged into the day rolled over to when the date arrives
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub planned: bool,
+    #[cfg_attr(feature = "serde", serde(default))]
     pub sections: Vec<Section>,
 }
 impl Day {
     pub fn new(date: NaiveDate) -> Day {
         let sections: Vec<Section> = Vec::new();
-        Day { date, sections }
+        Day {
+            date,
+            planned: false,
+            sections,
+        }
+    }
+
+    /// Date line of the day in date_format
+    pub fn header(&self, date_format: &str) -> String {
+        let date = self.date.format(date_format);
+        match self.planned {
+            true => format!("{date} {PLANNED_MARKER}"),
+            false => date.to_string(),
+        }
+    }
+
+    /// Section with the given name, created at the end of the day if it doesn't exist
+    pub fn section_mut(&mut self, name: &str) -> &mut Section {
+        let index = match self
+            .sections
+            .iter()
+            .position(|section| section.name == name)
+        {
+            Some(index) => index,
+            None => {
+                self.sections.push(Section::new(name));
+                self.sections.len() - 1
+            }
+        };
+        &mut self.sections[index]
+    }
+
+    /// Number of tasks in all sections of this day with the given status
+    pub fn count(&self, status: Status) -> usize {
+        self.sections
+            .iter()
+            .map(|section| section.count(status))
+            .sum()
+    }
+
+    /// Number of tasks in all sections of this day for each status
+    pub fn count_by_status(&self) -> HashMap<Status, usize> {
+        let mut counts = HashMap::new();
+        for section in &self.sections {
+            for (status, count) in section.count_by_status() {
+                *counts.entry(status).or_insert(0) += count;
+            }
+        }
+        counts
     }
 }
 
@@ -32,31 +92,77 @@ impl PartialOrd for Day {
 }
 
 impl str::FromStr for Day {
-    type Err = Box<dyn error::Error + Send + Sync>;
+    type Err = Error;
     fn from_str(s: &str) -> Result<Self> {
-        let text = s.trim().to_string();
+        let (day, errors) = Day::parse_lenient(s, DEFAULT_DATE_FORMAT)?;
+        match errors.into_iter().next() {
+            Some(e) => Err(e),
+            None => Ok(day),
+        }
+    }
+}
+
+impl Day {
+    /// Parses a day skipping lines that can't be parsed, returns the errors for those lines
+    ///
+    /// Fails only if the date can't be parsed with date_format
+    pub(crate) fn parse_lenient(s: &str, date_format: &str) -> Result<(Day, Vec<Error>)> {
         // first line must be the date
-        let mut lines = text.lines();
-        let date = NaiveDate::parse_from_str(lines.next().unwrap().trim(), "[%Y-%m-%d]")?;
+        let Some((header_index, header)) = s
+            .lines()
+            .enumerate()
+            .find(|(_, line)| !line.trim().is_empty())
+        else {
+            return err!("Unable to read day date");
+        };
+        let (date, planned) = parse_header(header, date_format);
+        let date = match date {
+            Ok(date) => date,
+            Err(_) => {
+                return Err(Error::InvalidDateHeader {
+                    header: header.trim().to_string(),
+                    location: Location::of_line(header_index, header),
+                })
+            }
+        };
 
-        let mut sections: Vec<Section> = Vec::new();
         // the rest of the lines should be sections with tasks
-        let section_iter = SectionIterator::new(&text);
-        for section in section_iter {
-            if let Err(e) = section {
-                return err!("Unable to parse day {date}: {e}");
+        let lines: Vec<&str> = s.lines().collect();
+        let mut sections: Vec<Section> = Vec::new();
+        let mut errors: Vec<Error> = Vec::new();
+        let mut index = header_index + 1;
+        while let Some(section_lines) = next_section_lines(&lines, index) {
+            index = section_lines.end;
+            let offset = |e: Error| e.offset_lines(section_lines.start);
+            match Section::parse_lenient(&lines[section_lines.clone()].join("\n")) {
+                Ok((section, section_errors)) => {
+                    sections.push(section);
+                    errors.extend(section_errors.into_iter().map(offset));
+                }
+                Err(e) => errors.push(offset(e)),
             }
-            sections.push(section?);
         }
-        Ok(Day { date, sections })
+        let day = Day {
+            date,
+            planned,
+            sections,
+        };
+        Ok((day, errors))
+    }
+}
+
+impl Day {
+    /// Writes the day with its header in date_format, Display uses the default format
+    pub fn render(&self, date_format: &str) -> String {
+        let header = self.header(date_format);
+        let sections = self.sections.iter().join("\n\n");
+        format!("{header}\n{sections}")
     }
 }
 
 impl fmt::Display for Day {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let date = self.date.format("[%Y-%m-%d]").to_string();
-        let sections = self.sections.iter().join("\n\n");
-        write!(f, "{date}\n{sections}")
+        write!(f, "{}", self.render(DEFAULT_DATE_FORMAT))
     }
 }
 
@@ -75,50 +181,59 @@ impl<'a> DayIterator<'a> {
 }
 
 impl<'a> Iterator for DayIterator<'a> {
-    type Item = Day;
+    type Item = Result<Day>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let mut day: Vec<&'a str> = Vec::new();
-        if self.index >= self.lines.len() {
-            None
-        } else {
-            // first line must be start of a day, consume anything that is not start of day
-            while self.index < self.lines.len() && !is_day_start(self.lines[self.index]) {
-                self.index += 1;
-            }
-            if self.index >= self.lines.len() {
-                return None;
-            }
+        let lines = next_day_lines(&self.lines, self.index, DEFAULT_DATE_FORMAT)?;
+        self.index = lines.end;
 
-            // read start of day
-            let start = self.lines[self.index];
-            day.push(start);
-            self.index += 1;
+        let day = self.lines[lines.clone()].join("\n").parse();
+        Some(day.map_err(|e: Error| e.offset_lines(lines.start)))
+    }
+}
 
-            // read everything until start of next day
-            while self.index < self.lines.len() && !is_day_start(self.lines[self.index]) {
-                day.push(self.lines[self.index]);
-                self.index += 1;
-            }
+/// Range of lines of the first day at or after index, lines before the start of a day are skipped
+pub(crate) fn next_day_lines(
+    lines: &[&str],
+    mut index: usize,
+    date_format: &str,
+) -> Option<Range<usize>> {
+    // first line must be start of a day, consume anything that is not start of day
+    while index < lines.len() && !is_day_start(lines[index], date_format) {
+        index += 1;
+    }
+    if index >= lines.len() {
+        return None;
+    }
 
-            let day: Day = day.join("\n").parse().expect("Unable to parse day");
-            Some(day)
-        }
+    // read start of day
+    let start = index;
+    index += 1;
+
+    // read everything until start of next day
+    while index < lines.len() && !is_day_start(lines[index], date_format) {
+        index += 1;
     }
+    Some(start..index)
 }
 
-fn is_day_start(line: &str) -> bool {
-    let line = line.trim();
-    if line.is_empty() {
+/// Unindented lines starting with `[` always start a day, so that invalid dates are reported
+pub(crate) fn is_day_start(line: &str, date_format: &str) -> bool {
+    // headers can't be indented, indented lines are notes
+    if line.starts_with([' ', '\t']) {
         return false;
     }
-    let first_char = line.chars().next();
-    if let Some(c) = first_char {
-        if c == '[' {
-            return true;
-        }
-    }
-    false
+    line.starts_with('[') || (!line.trim().is_empty() && parse_header(line, date_format).0.is_ok())
+}
+
+/// Date of a day header and whether it is marked as planned
+fn parse_header(header: &str, date_format: &str) -> (chrono::ParseResult<NaiveDate>, bool) {
+    let header = header.trim();
+    let (date, planned) = match header.strip_suffix(PLANNED_MARKER) {
+        Some(date) => (date.trim_end(), true),
+        None => (header, false),
+    };
+    (NaiveDate::parse_from_str(date, date_format), planned)
 }
 
 #[cfg(test)]
@@ -148,42 +263,23 @@ mod tests {
         let actual: Day = day_text.parse().expect("Unable to parse day");
         let expected = Day {
             date: NaiveDate::from_ymd_opt(2024, 3, 6).unwrap(),
+            planned: false,
             sections: vec![
                 Section {
                     name: "Section 1".to_string(),
                     tasks: vec![
-                        Task {
-                            text: "task 1".to_string(),
-                        },
-                        Task {
-                            text: "task 3".to_string(),
-                        },
-                        Task {
-                            text: "task 2".to_string(),
-                        },
+                        Task::new("task 1"),
+                        Task::new("task 3"),
+                        Task::new("task 2"),
                     ],
                 },
                 Section {
                     name: "Section 2".to_string(),
-                    tasks: vec![
-                        Task {
-                            text: "task 2.1".to_string(),
-                        },
-                        Task {
-                            text: "task 2.2".to_string(),
-                        },
-                    ],
+                    tasks: vec![Task::new("task 2.1"), Task::new("task 2.2")],
                 },
                 Section {
                     name: "Section 3".to_string(),
-                    tasks: vec![
-                        Task {
-                            text: "task 3.2".to_string(),
-                        },
-                        Task {
-                            text: "task 3.1".to_string(),
-                        },
-                    ],
+                    tasks: vec![Task::new("task 3.2"), Task::new("task 3.1")],
                 },
             ],
         };
@@ -209,48 +305,117 @@ mod tests {
         let actual: Day = day_text.parse().expect("Unable to parse day");
         let expected = Day {
             date: NaiveDate::from_ymd_opt(2024, 3, 6).unwrap(),
+            planned: false,
             sections: vec![
                 Section {
                     name: "".to_string(),
                     tasks: vec![
-                        Task {
-                            text: "task A".to_string(),
-                        },
-                        Task {
-                            text: "task B".to_string(),
-                        },
-                        Task {
-                            text: "task C".to_string(),
-                        },
+                        Task::new("task A"),
+                        Task::new("task B"),
+                        Task::new("task C"),
                     ],
                 },
                 Section {
                     name: "Section 2".to_string(),
-                    tasks: vec![
-                        Task {
-                            text: "task 2.1".to_string(),
-                        },
-                        Task {
-                            text: "task 2.2".to_string(),
-                        },
-                    ],
+                    tasks: vec![Task::new("task 2.1"), Task::new("task 2.2")],
                 },
                 Section {
                     name: "Section 3".to_string(),
-                    tasks: vec![
-                        Task {
-                            text: "task 3.2".to_string(),
-                        },
-                        Task {
-                            text: "task 3.1".to_string(),
-                        },
-                    ],
+                    tasks: vec![Task::new("task 3.2"), Task::new("task 3.1")],
                 },
             ],
         };
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn day_with_task_notes() {
+        let day_text = indoc! {"
+            [2024-03-06]
+            Section 1
+            - task 1
+              a note about task 1
+            Section 2
+            - task 2.1
+        "};
+
+        let actual: Day = day_text.parse().expect("Unable to parse day");
+        assert_eq!(actual.sections.len(), 2);
+        assert_eq!(
+            actual.sections[0].tasks[0].notes[0].text,
+            "a note about task 1"
+        );
+        assert_eq!(actual.sections[1].name, "Section 2");
+    }
+
+    #[test]
+    fn note_with_brackets() {
+        let day_text = indoc! {"
+            [2024-03-06]
+            - task 1
+              [docs](https://example.com)
+            [2024-03-07]
+        "};
+        let days: Vec<Day> = DayIterator::new(day_text).map(|day| day.unwrap()).collect();
+        assert_eq!(days.len(), 2);
+        let task = &days[0].sections[0].tasks[0];
+        assert_eq!(task.notes[0].text, "[docs](https://example.com)");
+        assert_eq!(task.to_string(), "- task 1\n  [docs](https://example.com)");
+    }
+
+    #[test]
+    fn get_or_create_section() {
+        let mut day: Day = "[2024-03-06]\nWork\n- task 1\n".parse().unwrap();
+        day.section_mut("Work").tasks.push(Task::new("task 2"));
+        day.section_mut("Home").tasks.push(Task::new("task 3"));
+        assert_eq!(day.sections.len(), 2);
+        assert_eq!(day.sections[0].tasks.len(), 2);
+        assert_eq!(day.sections[1].name, "Home");
+    }
+
+    #[test]
+    fn parse_day_date_format() {
+        let (day, errors) = Day::parse_lenient("07/03/2024\n- task 1\n", "%d/%m/%Y").unwrap();
+        assert!(errors.is_empty());
+        assert_eq!(day.date, NaiveDate::from_ymd_opt(2024, 3, 7).unwrap());
+        assert_eq!(day.render("%d/%m/%Y"), "07/03/2024\n\n- task 1");
+        assert!(is_day_start("07/03/2024", "%d/%m/%Y"));
+        assert!(!is_day_start("07/03/2024", DEFAULT_DATE_FORMAT));
+    }
+
+    #[test]
+    fn parse_planned_day() {
+        let day: Day = "[2024-12-31] (planned)\n- task 1\n".parse().unwrap();
+        assert!(day.planned);
+        assert_eq!(day.date, NaiveDate::from_ymd_opt(2024, 12, 31).unwrap());
+        assert_eq!(day.to_string(), "[2024-12-31] (planned)\n\n- task 1");
+        assert!(is_day_start("31/12/2024 (planned)", "%d/%m/%Y"));
+    }
+
+    #[test]
+    fn count_day_tasks() {
+        let day_text = indoc! {"
+            [2024-03-06]
+            Section 1
+            - [x] task 1
+            - [-] task 2
+            Section 2
+            - [!] task 2.1
+            - [x] task 2.2
+        "};
+
+        let day: Day = day_text.parse().expect("Unable to parse day");
+        assert_eq!(day.count(Status::Done), 2);
+        assert_eq!(day.count(Status::Blocked), 1);
+
+        let expected = HashMap::from([
+            (Status::Done, 2),
+            (Status::Cancelled, 1),
+            (Status::Blocked, 1),
+        ]);
+        assert_eq!(day.count_by_status(), expected);
+    }
+
     #[test]
     fn err_parse_day() {
         let day_text = indoc! {"
@@ -269,10 +434,8 @@ mod tests {
             - task 3.1
         "};
 
-        let day = "2024-03-06";
-        let section = "Section 1";
         let task = "[oops error here]";
-        let expected_error = format!("Unable to parse day {day}: Unable to parse section {section}: Unable to parse task: {task}");
+        let expected_error = format!("line 5, column 1: Unable to parse task: {task}");
 
         let actual: Result<Day> = day_text.parse();
 
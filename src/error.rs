@@ -0,0 +1,205 @@
+use std::{error, fmt, io, path::PathBuf};
+
+#[derive(Debug)]
+pub enum Error {
+    /// Reading or writing a file failed
+    Io { path: PathBuf, source: io::Error },
+    /// Line starting with `[` that is not a valid date
+    InvalidDateHeader { header: String, location: Location },
+    /// Line inside a section that is neither a task nor a note
+    InvalidTask { text: String, location: Location },
+    /// Task before the first day
+    OrphanTask { text: String, location: Location },
+    /// Saving would not change the file
+    UpToDate { path: PathBuf },
+    /// Anything else, e.g. a section or task that does not exist
+    Message(String),
+}
+
+/// Where in a todo file an error happened
+#[derive(PartialEq, Eq, Debug, Clone, Default)]
+pub struct Location {
+    pub path: Option<PathBuf>,
+    /// Line number, starting at 1
+    pub line: usize,
+    /// Column number, starting at 1
+    pub column: usize,
+    /// Number of characters the error spans
+    pub len: usize,
+    /// The line the error points at
+    pub source_line: String,
+}
+
+impl Location {
+    /// Location spanning the trimmed contents of line, line_index starts at 0
+    pub(crate) fn of_line(line_index: usize, line: &str) -> Location {
+        let trimmed = line.trim_start();
+        Location {
+            path: None,
+            line: line_index + 1,
+            column: line[..line.len() - trimmed.len()].chars().count() + 1,
+            len: trimmed.trim_end().chars().count(),
+            source_line: line.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.path {
+            Some(path) => write!(f, "{}:{}:{}", path.display(), self.line, self.column),
+            None => write!(f, "line {}, column {}", self.line, self.column),
+        }
+    }
+}
+
+impl Error {
+    /// Reading or writing the file at path failed with source
+    pub fn io(path: impl Into<PathBuf>, source: io::Error) -> Error {
+        Error::Io {
+            path: path.into(),
+            source,
+        }
+    }
+
+    pub fn location(&self) -> Option<&Location> {
+        match self {
+            Error::InvalidDateHeader { location, .. }
+            | Error::InvalidTask { location, .. }
+            | Error::OrphanTask { location, .. } => Some(location),
+            _ => None,
+        }
+    }
+
+    /// Sets the file the error happened in
+    pub fn with_path(mut self, path: impl Into<PathBuf>) -> Error {
+        if let Some(location) = self.location_mut() {
+            location.path = Some(path.into());
+        }
+        self
+    }
+
+    /// Moves the error down by offset lines, used when parsing part of a file
+    pub(crate) fn offset_lines(mut self, offset: usize) -> Error {
+        if let Some(location) = self.location_mut() {
+            location.line += offset;
+        }
+        self
+    }
+
+    fn location_mut(&mut self) -> Option<&mut Location> {
+        match self {
+            Error::InvalidDateHeader { location, .. }
+            | Error::InvalidTask { location, .. }
+            | Error::OrphanTask { location, .. } => Some(location),
+            _ => None,
+        }
+    }
+
+    /// Error message without its location
+    pub fn message(&self) -> String {
+        match self {
+            Error::Io { path, source } => format!("Unable to access {}: {source}", path.display()),
+            Error::InvalidDateHeader { header, .. } => format!("Invalid date header: {header}"),
+            Error::InvalidTask { text, .. } => format!("Unable to parse task: {text}"),
+            Error::OrphanTask { text, .. } => format!("Task outside of a day: {text}"),
+            Error::UpToDate { path } => format!("File already up to date: {}", path.display()),
+            Error::Message(message) => message.clone(),
+        }
+    }
+
+    /// Renders the error the way rustc does, pointing at the offending line
+    ///
+    /// ```text
+    /// error: Invalid date header: [2024-13-01]
+    ///  --> todo.txt:3:1
+    ///   |
+    /// 3 | [2024-13-01]
+    ///   | ^^^^^^^^^^^^
+    /// ```
+    pub fn render(&self) -> String {
+        let message = self.message();
+        match self.location() {
+            Some(location) => render_diagnostic("error", &message, location),
+            None => format!("error: {message}"),
+        }
+    }
+}
+
+/// Renders a message pointing at location, e.g. `error: ...` followed by the source line
+pub(crate) fn render_diagnostic(level: &str, message: &str, location: &Location) -> String {
+    let number = location.line.to_string();
+    let gutter = " ".repeat(number.len());
+    // keep tabs so the carets line up with the source line, column 0 is read as 1
+    let padding: String = location
+        .source_line
+        .chars()
+        .take(location.column.saturating_sub(1))
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+    let carets = "^".repeat(location.len.max(1));
+    let source_line = &location.source_line;
+
+    format!(
+        "{level}: {message}\n{gutter}--> {location}\n{gutter} |\n{number} | {source_line}\n{gutter} | {padding}{carets}"
+    )
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.location() {
+            Some(location) => write!(f, "{location}: {}", self.message()),
+            None => write!(f, "{}", self.message()),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn render_error() {
+        let error = Error::InvalidDateHeader {
+            header: "[2024-13-01]".to_string(),
+            location: Location::of_line(11, "  [2024-13-01]"),
+        }
+        .with_path("todo.txt");
+
+        let expected = [
+            "error: Invalid date header: [2024-13-01]",
+            "  --> todo.txt:12:3",
+            "   |",
+            "12 |   [2024-13-01]",
+            "   |   ^^^^^^^^^^^^",
+        ]
+        .join("\n");
+        assert_eq!(error.render(), expected);
+        assert_eq!(
+            error.to_string(),
+            "todo.txt:12:3: Invalid date header: [2024-13-01]"
+        );
+    }
+
+    #[test]
+    fn render_default_location() {
+        let rendered = render_diagnostic("warning", "Empty file", &Location::default());
+        assert!(rendered.ends_with("0 | \n  | ^"));
+    }
+
+    #[test]
+    fn render_error_without_location() {
+        let error = Error::Message("Unable to find section Work".to_string());
+        assert_eq!(error.render(), "error: Unable to find section Work");
+    }
+}
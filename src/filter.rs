@@ -0,0 +1,83 @@
+//! Which days and sections of a todo are exported
+
+use crate::Todo;
+use chrono::NaiveDate;
+
+#[derive(PartialEq, Eq, Debug, Clone, Default)]
+pub struct Filter {
+    /// First day kept
+    pub from: Option<NaiveDate>,
+    /// Last day kept
+    pub to: Option<NaiveDate>,
+    /// Names of the sections kept, every section when empty
+    pub sections: Vec<String>,
+}
+
+impl Filter {
+    pub fn matches_date(&self, date: NaiveDate) -> bool {
+        self.from.is_none_or(|from| date >= from) && self.to.is_none_or(|to| date <= to)
+    }
+
+    pub fn matches_section(&self, name: &str) -> bool {
+        self.sections.is_empty() || self.sections.iter().any(|section| section == name)
+    }
+
+    /// Copy of todo with only the days and sections that match, days left without sections are dropped
+    pub fn apply(&self, todo: &Todo) -> Todo {
+        let mut todo = todo.clone();
+        todo.days.retain(|date, _| self.matches_date(*date));
+        for day in todo.days.values_mut() {
+            day.sections
+                .retain(|section| self.matches_section(&section.name));
+        }
+        if !self.sections.is_empty() {
+            todo.days.retain(|_, day| !day.sections.is_empty());
+        }
+        todo
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use indoc::indoc;
+
+    #[test]
+    fn filter_days_and_sections() {
+        let todo: Todo = indoc! {"
+            [2024-03-08]
+            Home
+            - laundry
+
+            [2024-03-07]
+            Work
+            - deploy
+            Home
+            - groceries
+
+            [2024-03-01]
+            Work
+            - review
+        "}
+        .parse()
+        .unwrap();
+        let date = |d| NaiveDate::from_ymd_opt(2024, 3, d).unwrap();
+
+        let filter = Filter {
+            from: Some(date(2)),
+            sections: vec!["Work".to_string()],
+            ..Default::default()
+        };
+        let filtered = filter.apply(&todo);
+        assert_eq!(filtered.to_string(), "[2024-03-07]\nWork\n- deploy");
+
+        let filter = Filter {
+            to: Some(date(7)),
+            ..Default::default()
+        };
+        let mut dates: Vec<NaiveDate> = filter.apply(&todo).days.into_keys().collect();
+        dates.sort();
+        assert_eq!(dates, vec![date(1), date(7)]);
+    }
+}
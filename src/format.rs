@@ -0,0 +1,236 @@
+//! Canonical formatting of todo files
+
+use crate::{
+    config::Config, day::Day, err, section::Section, task::indent_width, util::Result, Note, Task,
+    Todo,
+};
+use itertools::Itertools;
+use std::{collections::HashSet, path::Path};
+
+/// Todo in canonical form
+///
+/// Days are written newest first with blank lines between days and between sections, tasks start
+/// with `- ` (or `- [x] `, never `- [X] `), tabs are expanded and trailing whitespace is removed, following the
+/// format settings of the todo's config. Text before the first day is kept. Fails if a date appears
+/// more than once, since only one of them would be kept.
+pub fn format(todo: &Todo) -> Result<String> {
+    let mut blocks: Vec<String> = Vec::new();
+    if let Some(tree) = todo.syntax_tree() {
+        let mut seen = HashSet::new();
+        if let Some(node) = tree.days.iter().find(|node| !seen.insert(node.day.date)) {
+            return err!(
+                "Unable to format: date {} appears more than once",
+                node.day.date
+            );
+        }
+        let preamble = tree.text(tree.preamble.clone());
+        if !preamble.trim().is_empty() {
+            blocks.push(preamble);
+        }
+    }
+    let config = &todo.config;
+    let days = todo.days.values().sorted_by(|a, b| b.date.cmp(&a.date));
+    blocks.extend(days.map(|day| format_day(day, config)));
+
+    // the same number of blank lines between blocks, and none at the start or end of the file
+    let separator = "\n".repeat(config.format.blank_lines + 1);
+    let mut lines: Vec<String> = Vec::new();
+    let mut blanks = 0;
+    for line in blocks.join(&separator).lines() {
+        let line = expand_tabs(line.trim_end(), config.format.tab_width);
+        if line.is_empty() {
+            blanks += 1;
+            if lines.is_empty() || blanks > config.format.blank_lines {
+                continue;
+            }
+        } else {
+            blanks = 0;
+        }
+        lines.push(line);
+    }
+    while lines.last().is_some_and(|last| last.is_empty()) {
+        lines.pop();
+    }
+    Ok(lines.into_iter().map(|line| line + "\n").collect())
+}
+
+fn format_day(day: &Day, config: &Config) -> String {
+    let header = day.header(&config.date_format);
+    let separator = "\n".repeat(config.format.blank_lines + 1);
+    let sections = day
+        .sections
+        .iter()
+        .filter(|section| !section.name.is_empty() || !section.tasks.is_empty())
+        .map(format_section)
+        .join(&separator);
+    match sections.is_empty() {
+        true => header,
+        false => format!("{header}\n{sections}"),
+    }
+}
+
+fn format_section(section: &Section) -> String {
+    // anonymous sections have no name line
+    let name = (!section.name.is_empty()).then_some(section.name.trim().to_string());
+    name.into_iter()
+        .chain(section.tasks.iter().map(|task| canonical(task).to_string()))
+        .join("\n")
+}
+
+/// Copy of task and its subtasks written with the usual status markers, with notes two spaces
+/// deeper than their task while keeping their indentation relative to each other
+fn canonical(task: &Task) -> Task {
+    let widths = task.notes.iter().filter(|note| !note.text.is_empty());
+    let least = widths.map(|note| indent_width(&note.indent)).min();
+    let notes = task.notes.iter().map(|note| match note.text.is_empty() {
+        true => note.clone(),
+        false => Note {
+            indent: format!(
+                "{}  {}",
+                task.indent,
+                " ".repeat(indent_width(&note.indent) - least.unwrap_or(0))
+            ),
+            ..note.clone()
+        },
+    });
+    Task {
+        marker: None,
+        notes: notes.collect(),
+        subtasks: task.subtasks.iter().map(canonical).collect(),
+        ..task.clone()
+    }
+}
+
+fn expand_tabs(line: &str, tab_width: usize) -> String {
+    let mut expanded = String::with_capacity(line.len());
+    let mut column = 0;
+    for c in line.chars() {
+        if c == '\t' {
+            let width = tab_width - column % tab_width;
+            expanded.extend(std::iter::repeat_n(' ', width));
+            column += width;
+        } else {
+            expanded.push(c);
+            column += 1;
+        }
+    }
+    expanded
+}
+
+/// Unified diff from the file as it is to its formatted contents
+pub fn diff(path: &Path, original: &str, formatted: &str) -> String {
+    let name = path.display().to_string();
+    similar::TextDiff::from_lines(original, formatted)
+        .unified_diff()
+        .header(&name, &name)
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use indoc::indoc;
+
+    #[test]
+    fn format_todo() {
+        let todo: Todo = indoc! {"
+            Notes before the first day
+
+
+            [2024-03-06]
+            -task 1
+            Work
+            -   [X] task 2
+            \t- subtask\t
+            Done
+
+            [2024-03-07]
+
+
+            Work
+            - task 3
+            \t\tnote
+            Done
+            - [x] task 4
+        "}
+        .parse()
+        .unwrap();
+
+        let expected = indoc! {"
+            Notes before the first day
+
+            [2024-03-07]
+            Work
+            - task 3
+              note
+
+            Done
+            - [x] task 4
+
+            [2024-03-06]
+            - task 1
+
+            Work
+            - [x] task 2
+                - subtask
+
+            Done
+        "};
+        let formatted = format(&todo).unwrap();
+        assert_eq!(formatted, expected);
+
+        // formatting formatted text changes nothing
+        let todo: Todo = formatted.parse().unwrap();
+        assert_eq!(format(&todo).unwrap(), expected);
+    }
+
+    #[test]
+    fn format_with_config() {
+        let config = Config {
+            date_format: "%d/%m/%Y".to_string(),
+            format: crate::config::Format {
+                tab_width: 2,
+                blank_lines: 0,
+            },
+            ..Default::default()
+        };
+        let (todo, errors) = Todo::parse_lenient_with(
+            "06/03/2024\nWork\n- task 1\n\tnote\n\n\nDone\n\n07/03/2024\n- task 2\n",
+            &config,
+        );
+        assert!(errors.is_empty());
+        let expected = "07/03/2024\n- task 2\n06/03/2024\nWork\n- task 1\n  note\nDone\n";
+        assert_eq!(format(&todo).unwrap(), expected);
+    }
+
+    #[test]
+    fn err_format_duplicate_date() {
+        let todo: Todo = "[2024-03-06]\n- task 1\n\n[2024-03-06]\n- task 2\n"
+            .parse()
+            .unwrap();
+        let actual = format(&todo).unwrap_err().to_string();
+        assert_eq!(
+            actual,
+            "Unable to format: date 2024-03-06 appears more than once"
+        );
+    }
+
+    #[test]
+    fn diff_formatted() {
+        let actual = diff(
+            Path::new("todo.txt"),
+            "[2024-03-06]\n-task\n",
+            "[2024-03-06]\n- task\n",
+        );
+        let expected = indoc! {"
+            --- todo.txt
+            +++ todo.txt
+            @@ -1,2 +1,2 @@
+             [2024-03-06]
+            --task
+            +- task
+        "};
+        assert_eq!(actual, expected);
+    }
+}
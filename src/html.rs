@@ -0,0 +1,408 @@
+//! Static HTML site of todos
+//!
+//! The site is a set of files that work when opened from disk:
+//!
+//! - `index.html` with a calendar of every month that has days, links to section pages and a search box
+//! - `days/2024-03-07.html` for every day
+//! - `sections/work.html` for every named section, with its tasks on every day it appears
+//! - `search-index.js` with the text of every task, used by the search box
+//! - `style.css`
+
+use crate::{error::Error, util::Result, Day, Task, Todo};
+use chrono::{Datelike, Months, NaiveDate};
+use itertools::Itertools;
+use std::{collections::HashMap, fs, path::Path, path::PathBuf};
+
+static STYLE: &str = "\
+body { font-family: sans-serif; max-width: 50em; margin: 2em auto; padding: 0 1em; color: #222; }
+a { color: #0a58ca; }
+nav { margin-bottom: 1em; }
+ul.tasks { list-style: none; padding-left: 1.5em; }
+ul.tasks li::before { margin-left: -1.5em; width: 1.5em; display: inline-block; font-family: monospace; }
+li.open::before { content: '[ ]'; }
+li.done::before { content: '[x]'; }
+li.in-progress::before { content: '[~]'; }
+li.blocked::before { content: '[!]'; }
+li.cancelled::before { content: '[-]'; }
+li.deferred::before { content: '[>]'; }
+li.done > .text, li.cancelled > .text { color: #777; }
+li.cancelled > .text { text-decoration: line-through; }
+.note { color: #555; margin: 0; }
+.planned { color: #777; font-weight: normal; }
+table.month { border-collapse: collapse; display: inline-table; margin: 0 1em 1em 0; vertical-align: top; }
+table.month td, table.month th { width: 2em; text-align: center; padding: 0.2em; }
+table.month td a { font-weight: bold; }
+#results li { margin-bottom: 0.3em; }
+";
+
+static SEARCH: &str = "\
+const input = document.getElementById('search');
+const results = document.getElementById('results');
+input.addEventListener('input', () => {
+  const words = input.value.toLowerCase().split(/\\s+/).filter(word => word);
+  results.replaceChildren();
+  if (words.length === 0) return;
+  const hits = SEARCH_INDEX.filter(entry => {
+    const text = entry.text.toLowerCase();
+    return words.every(word => text.includes(word));
+  });
+  for (const hit of hits.slice(0, 100)) {
+    const item = document.createElement('li');
+    const link = document.createElement('a');
+    link.href = hit.url;
+    link.textContent = hit.date + (hit.section ? ' ' + hit.section : '');
+    item.append(link, ' ', hit.text);
+    results.append(item);
+  }
+});
+";
+
+/// Files of the site, with their path in the site
+pub fn site(todo: &Todo) -> Vec<(PathBuf, String)> {
+    let days: Vec<&Day> = todo
+        .days
+        .values()
+        .sorted_by(|a, b| b.date.cmp(&a.date))
+        .collect();
+    let slugs = section_slugs(&days);
+
+    let mut files = vec![
+        (PathBuf::from("index.html"), index_page(&days, &slugs)),
+        (PathBuf::from("style.css"), STYLE.to_string()),
+        (PathBuf::from("search-index.js"), search_index(&days)),
+    ];
+    for day in &days {
+        let path = Path::new("days").join(format!("{}.html", day.date.format("%Y-%m-%d")));
+        files.push((path, day_page(day, &slugs)));
+    }
+    for (name, slug) in slugs.iter().sorted_by_key(|(_, slug)| slug.as_str()) {
+        let path = Path::new("sections").join(format!("{slug}.html"));
+        files.push((path, section_page(name, &days)));
+    }
+    files
+}
+
+/// Writes the site to dir, creating it if needed and replacing files that are already there
+pub fn write_site(todo: &Todo, dir: &Path) -> Result<()> {
+    for (path, contents) in site(todo) {
+        let path = dir.join(path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| Error::io(parent, e))?;
+        }
+        fs::write(&path, contents).map_err(|e| Error::io(&path, e))?;
+    }
+    Ok(())
+}
+
+/// File name of the page of every named section, unique even when names only differ in case or punctuation
+fn section_slugs(days: &[&Day]) -> HashMap<String, String> {
+    let mut slugs: HashMap<String, String> = HashMap::new();
+    let names = days
+        .iter()
+        .flat_map(|day| &day.sections)
+        .map(|section| section.name.trim())
+        .filter(|name| !name.is_empty())
+        .unique()
+        .sorted();
+    for name in names {
+        let base: String = name
+            .to_lowercase()
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .join("-");
+        let base = if base.is_empty() { "section" } else { &base };
+        let mut slug = base.to_string();
+        let mut n = 1;
+        while slugs.values().any(|used| *used == slug) {
+            n += 1;
+            slug = format!("{base}-{n}");
+        }
+        slugs.insert(name.to_string(), slug);
+    }
+    slugs
+}
+
+fn page(title: &str, root: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+         <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n\
+         <title>{}</title>\n<link rel=\"stylesheet\" href=\"{root}style.css\">\n</head>\n\
+         <body>\n{body}</body>\n</html>\n",
+        escape(title)
+    )
+}
+
+fn index_page(days: &[&Day], slugs: &HashMap<String, String>) -> String {
+    let mut body = String::from("<h1>netxt</h1>\n");
+    body.push_str("<input id=\"search\" type=\"search\" placeholder=\"Search tasks\" autofocus>\n");
+    body.push_str("<ul id=\"results\"></ul>\n");
+
+    if !slugs.is_empty() {
+        body.push_str("<h2>Sections</h2>\n<ul>\n");
+        for (name, slug) in slugs.iter().sorted_by_key(|(name, _)| name.to_lowercase()) {
+            body.push_str(&format!(
+                "<li><a href=\"sections/{slug}.html\">{}</a></li>\n",
+                escape(name)
+            ));
+        }
+        body.push_str("</ul>\n");
+    }
+
+    body.push_str("<h2>Days</h2>\n");
+    let dates: HashMap<NaiveDate, &Day> = days.iter().map(|day| (day.date, *day)).collect();
+    let months = days
+        .iter()
+        .map(|day| day.date.with_day(1).unwrap_or(day.date))
+        .unique();
+    for month in months {
+        body.push_str(&month_table(month, &dates));
+    }
+
+    body.push_str("<script src=\"search-index.js\"></script>\n");
+    body.push_str(&format!("<script>\n{SEARCH}</script>\n"));
+    page("netxt", "", &body)
+}
+
+/// Calendar of the month starting on first, days in dates link to their page
+fn month_table(first: NaiveDate, dates: &HashMap<NaiveDate, &Day>) -> String {
+    let mut out = format!(
+        "<table class=\"month\">\n<caption>{}</caption>\n<tr>",
+        first.format("%B %Y")
+    );
+    for weekday in ["Mo", "Tu", "We", "Th", "Fr", "Sa", "Su"] {
+        out.push_str(&format!("<th>{weekday}</th>"));
+    }
+    out.push_str("</tr>\n<tr>");
+    let offset = first.weekday().num_days_from_monday() as usize;
+    out.push_str(&"<td></td>".repeat(offset));
+    let next_month = first + Months::new(1);
+    for (i, date) in first
+        .iter_days()
+        .take_while(|d| *d < next_month)
+        .enumerate()
+    {
+        if i > 0 && (i + offset).is_multiple_of(7) {
+            out.push_str("</tr>\n<tr>");
+        }
+        let day = date.day();
+        match dates.get(&date) {
+            Some(found) => {
+                let title = match found.planned {
+                    true => " title=\"planned\"",
+                    false => "",
+                };
+                out.push_str(&format!(
+                    "<td><a href=\"days/{}.html\"{title}>{day}</a></td>",
+                    date.format("%Y-%m-%d")
+                ))
+            }
+            None => out.push_str(&format!("<td>{day}</td>")),
+        }
+    }
+    out.push_str("</tr>\n</table>\n");
+    out
+}
+
+fn day_page(day: &Day, slugs: &HashMap<String, String>) -> String {
+    let date = day.date.format("%Y-%m-%d").to_string();
+    let mut body = String::from("<nav><a href=\"../index.html\">All days</a></nav>\n");
+    body.push_str(&format!("<h1>{date}{}</h1>\n", planned_label(day)));
+    for section in &day.sections {
+        let name = section.name.trim();
+        if let Some(slug) = slugs.get(name) {
+            body.push_str(&format!(
+                "<h2><a href=\"../sections/{slug}.html\">{}</a></h2>\n",
+                escape(name)
+            ));
+        }
+        body.push_str(&tasks_list(&section.tasks));
+    }
+    page(&date, "../", &body)
+}
+
+fn section_page(name: &str, days: &[&Day]) -> String {
+    let mut body = String::from("<nav><a href=\"../index.html\">All days</a></nav>\n");
+    body.push_str(&format!("<h1>{}</h1>\n", escape(name)));
+    for day in days {
+        let sections = day
+            .sections
+            .iter()
+            .filter(|section| section.name.trim() == name);
+        for (i, section) in sections.enumerate() {
+            if i == 0 {
+                let date = day.date.format("%Y-%m-%d");
+                body.push_str(&format!(
+                    "<h2><a href=\"../days/{date}.html\">{date}</a>{}</h2>\n",
+                    planned_label(day)
+                ));
+            }
+            body.push_str(&tasks_list(&section.tasks));
+        }
+    }
+    page(name, "../", &body)
+}
+
+fn planned_label(day: &Day) -> &'static str {
+    match day.planned {
+        true => " <span class=\"planned\">(planned)</span>",
+        false => "",
+    }
+}
+
+fn tasks_list(tasks: &[Task]) -> String {
+    if tasks.is_empty() {
+        return String::new();
+    }
+    let mut out = String::from("<ul class=\"tasks\">\n");
+    for task in tasks {
+        out.push_str(&format!(
+            "<li class=\"{}\"><span class=\"text\">{}</span>",
+            task.effective_status(),
+            escape(&task.text)
+        ));
+        for note in task.notes.iter().filter(|note| !note.text.is_empty()) {
+            out.push_str(&format!("\n<p class=\"note\">{}</p>", escape(&note.text)));
+        }
+        if !task.subtasks.is_empty() {
+            out.push('\n');
+            out.push_str(&tasks_list(&task.subtasks));
+        }
+        out.push_str("</li>\n");
+    }
+    out.push_str("</ul>\n");
+    out
+}
+
+/// Script defining SEARCH_INDEX, a script instead of JSON so that browsers load it from disk
+fn search_index(days: &[&Day]) -> String {
+    let mut out = String::from("const SEARCH_INDEX = [\n");
+    for day in days {
+        let date = day.date.format("%Y-%m-%d").to_string();
+        for section in &day.sections {
+            for task in section.iter_tasks() {
+                let text = task
+                    .notes
+                    .iter()
+                    .fold(task.text.clone(), |text, note| text + " " + &note.text);
+                out.push_str(&format!(
+                    "{{\"date\":{},\"section\":{},\"text\":{},\"url\":{}}},\n",
+                    js_string(&date),
+                    js_string(section.name.trim()),
+                    js_string(&text),
+                    js_string(&format!("days/{date}.html")),
+                ));
+            }
+        }
+    }
+    out.push_str("];\n");
+    out
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+/// Quoted string that can be placed in a script, `<` is escaped so that text can't close the script
+fn js_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '<' => out.push_str("\\u003c"),
+            c if (c as u32) < 0x20 || c == '\u{2028}' || c == '\u{2029}' => {
+                out.push_str(&format!("\\u{:04x}", c as u32))
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use indoc::indoc;
+    use tempfile::tempdir;
+
+    fn todo() -> Todo {
+        indoc! {"
+            [2024-03-07]
+            Work
+            - [x] deploy <script>
+              - build
+            Work!
+            - review
+              - [x] proofread
+
+            [2024-02-28]
+            Work
+            - [-] meeting \"weekly\"
+            - lunch
+        "}
+        .parse()
+        .unwrap()
+    }
+
+    #[test]
+    fn site_pages() {
+        let files: HashMap<PathBuf, String> = site(&todo()).into_iter().collect();
+        let paths: Vec<&str> = files
+            .keys()
+            .filter_map(|path| path.to_str())
+            .sorted()
+            .collect();
+        assert_eq!(
+            paths,
+            vec![
+                "days/2024-02-28.html",
+                "days/2024-03-07.html",
+                "index.html",
+                "search-index.js",
+                "sections/work-2.html",
+                "sections/work.html",
+                "style.css",
+            ]
+        );
+
+        let index = &files[Path::new("index.html")];
+        assert!(index.contains("<caption>March 2024</caption>"));
+        assert!(index.contains("<caption>February 2024</caption>"));
+        assert!(index.contains("<td><a href=\"days/2024-03-07.html\">7</a></td>"));
+
+        let day = &files[Path::new("days/2024-03-07.html")];
+        assert!(
+            day.contains("<li class=\"done\"><span class=\"text\">deploy &lt;script&gt;</span>")
+        );
+        assert!(day.contains("<a href=\"../sections/work-2.html\">Work!</a>"));
+        // done by its subtasks
+        assert!(day.contains("<li class=\"done\"><span class=\"text\">review</span>"));
+
+        // every Work section, but not Work!
+        let section = &files[Path::new("sections/work.html")];
+        assert!(section.contains("deploy") && section.contains("meeting"));
+        assert!(!section.contains("review"));
+
+        let search = &files[Path::new("search-index.js")];
+        assert!(search.contains(
+            "{\"date\":\"2024-02-28\",\"section\":\"Work\",\"text\":\"meeting \\\"weekly\\\"\",\"url\":\"days/2024-02-28.html\"}"
+        ));
+        assert!(search.contains("deploy \\u003cscript>"));
+    }
+
+    #[test]
+    fn write_site_to_dir() {
+        let dir = tempdir().unwrap();
+        let out = dir.path().join("site");
+        write_site(&todo(), &out).unwrap();
+        assert!(out.join("index.html").is_file());
+        assert!(out.join("days").join("2024-02-28.html").is_file());
+    }
+}
@@ -0,0 +1,426 @@
+//! iCalendar (RFC 5545) export of dated tasks
+//!
+//! Tasks are exported when they have a date or a time in their properties:
+//!
+//! - `due:2024-05-01` and `scheduled:2024-04-29` make a VTODO with that due and start date
+//! - a time, e.g. `scheduled:2024-04-29T14:00` or `at:14:00`, makes a VEVENT instead, on the date
+//!   of the property or of the day the task is in, lasting `est:1h30m` or an hour
+//!
+//! Tasks are carried from day to day. Tasks with a due or scheduled date are the same entry on
+//! every day they appear in and only their newest copy is exported, their UID is a hash of the
+//! section and the texts of the task and the tasks above it, without dates, so that calendars
+//! update entries when tasks change status or move to another date. Editing the text makes a new
+//! entry unless the task has an `id:` property, the UID is then a hash of the id alone. Events
+//! placed with `at:` alone happen on every day they appear in, the date is part of their UID.
+//!
+//! SEQUENCE is the number of days from 1970-01-01 to the newest day an entry appears in, so it
+//! grows as days are added whatever range is exported. DTSTAMP is the time of the export.
+
+use crate::{address::stable_hash, Section, Status, Task, Todo};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use itertools::Itertools;
+use std::collections::HashSet;
+
+/// Properties that place a task in the calendar, they are not part of its summary or UID
+static DATE_PROPERTIES: [&str; 3] = ["due", "scheduled", "at"];
+/// Property that names a task for good, it is not part of its summary
+static ID_PROPERTY: &str = "id";
+/// Property with the duration of an event, it is not part of its summary
+static DURATION_PROPERTY: &str = "est";
+
+/// Calendar with a VTODO or VEVENT for every dated task of todo
+pub fn to_string(todo: &Todo) -> String {
+    to_string_at(todo, Utc::now())
+}
+
+/// Same as [to_string], with now as the time the calendar is written at (DTSTAMP)
+pub fn to_string_at(todo: &Todo, now: DateTime<Utc>) -> String {
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        format!("PRODID:-//netxt//netxt {}//EN", env!("CARGO_PKG_VERSION")),
+        "CALSCALE:GREGORIAN".to_string(),
+    ];
+    let mut seen = HashSet::new();
+    for day in todo.days.values().sorted_by(|a, b| b.date.cmp(&a.date)) {
+        // days since 1970-01-01, the default date
+        let sequence = (day.date - NaiveDate::default()).num_days();
+        for section in &day.sections {
+            let mut tasks = Vec::new();
+            with_parents(&section.tasks, &[], &mut tasks);
+            for (parents, task) in tasks {
+                let uid = uid(day.date, section, &parents, task);
+                if seen.contains(&uid) {
+                    continue;
+                }
+                let Some(component) = component(day.date, section, task) else {
+                    continue;
+                };
+                let (begin, rest) = component
+                    .split_first()
+                    .expect("components start with BEGIN");
+                lines.push(begin.clone());
+                lines.push(format!("UID:{uid}"));
+                lines.push(format!("DTSTAMP:{}", now.format("%Y%m%dT%H%M%SZ")));
+                lines.push(format!("SEQUENCE:{sequence}"));
+                lines.extend(rest.iter().cloned());
+                seen.insert(uid);
+            }
+        }
+    }
+    lines.push("END:VCALENDAR".to_string());
+    lines.iter().map(|line| fold(line) + "\r\n").collect()
+}
+
+/// Every task in tasks (and subtasks) with the texts of the tasks above it, depth first
+fn with_parents<'a>(
+    tasks: &'a [Task],
+    parents: &[&'a str],
+    out: &mut Vec<(Vec<&'a str>, &'a Task)>,
+) {
+    for task in tasks {
+        out.push((parents.to_vec(), task));
+        let mut parents = parents.to_vec();
+        parents.push(&task.text);
+        with_parents(&task.subtasks, &parents, out);
+    }
+}
+
+/// UID of task, which is in a day with date below the tasks with the texts in parents
+fn uid(date: NaiveDate, section: &Section, parents: &[&str], task: &Task) -> String {
+    let key = match task.property(ID_PROPERTY) {
+        Some(id) => id.to_string(),
+        None => {
+            let path = parents
+                .iter()
+                .copied()
+                .chain([summary(task).as_str()])
+                .join("/");
+            let dated = ["due", "scheduled"]
+                .iter()
+                .any(|key| task.property(key).and_then(parse_date).is_some());
+            match dated {
+                true => format!("{}/{path}", section.name),
+                // events that happen every day they appear in
+                false => format!("{date}/{}/{path}", section.name),
+            }
+        }
+    };
+    format!("{:016x}@netxt", stable_hash(&key))
+}
+
+/// Task text without the properties that date, name or time it
+fn summary(task: &Task) -> String {
+    task.text
+        .split_whitespace()
+        .filter(|word| {
+            !word.split_once(':').is_some_and(|(key, _)| {
+                DATE_PROPERTIES.contains(&key) || key == ID_PROPERTY || key == DURATION_PROPERTY
+            })
+        })
+        .join(" ")
+}
+
+/// Lines of the VTODO or VEVENT of task, which is in a day with date, if it is dated.
+/// UID, DTSTAMP and SEQUENCE are left out, they don't change with the task
+fn component(date: NaiveDate, section: &Section, task: &Task) -> Option<Vec<String>> {
+    let due = task.property("due").and_then(parse_date);
+    let scheduled = task.property("scheduled").and_then(parse_date);
+    let at = task
+        .property("at")
+        .and_then(|at| NaiveTime::parse_from_str(at, "%H:%M").ok());
+
+    // a time anywhere makes the task an event
+    let start = match (scheduled.or(due), at) {
+        (Some(Dated::Time(time)), _) => Some(time),
+        (Some(Dated::Day(day)), Some(at)) => Some(day.and_time(at)),
+        (None, Some(at)) => Some(date.and_time(at)),
+        _ => None,
+    };
+
+    let mut lines = Vec::new();
+    match start {
+        Some(start) => {
+            lines.push("BEGIN:VEVENT".to_string());
+            lines.push(format!("DTSTART:{}", start.format("%Y%m%dT%H%M%S")));
+            let duration = task.property(DURATION_PROPERTY).and_then(parse_duration);
+            lines.push(format!(
+                "DURATION:{}",
+                duration.unwrap_or("PT1H".to_string())
+            ));
+            let status = match task.status {
+                Status::Cancelled => "CANCELLED",
+                _ => "CONFIRMED",
+            };
+            lines.push(format!("STATUS:{status}"));
+        }
+        None if due.is_some() || scheduled.is_some() => {
+            lines.push("BEGIN:VTODO".to_string());
+            if let Some(scheduled) = scheduled {
+                lines.push(format!("DTSTART;VALUE=DATE:{}", scheduled.format()));
+            }
+            if let Some(due) = due {
+                lines.push(format!("DUE;VALUE=DATE:{}", due.format()));
+            }
+            let status = match task.status {
+                _ if task.is_done() => "COMPLETED",
+                Status::InProgress => "IN-PROCESS",
+                Status::Cancelled => "CANCELLED",
+                _ => "NEEDS-ACTION",
+            };
+            lines.push(format!("STATUS:{status}"));
+        }
+        None => return None,
+    }
+
+    lines.push(format!("SUMMARY:{}", escape(&summary(task))));
+    if !task.notes.is_empty() {
+        let notes = task.notes.iter().map(|note| note.text.as_str()).join("\n");
+        lines.push(format!("DESCRIPTION:{}", escape(&notes)));
+    }
+    let categories: Vec<String> = (!section.name.is_empty())
+        .then(|| section.name.trim().to_string())
+        .into_iter()
+        .chain(task.tags.iter().cloned())
+        .map(|category| escape(&category))
+        .collect();
+    if !categories.is_empty() {
+        lines.push(format!("CATEGORIES:{}", categories.join(",")));
+    }
+    lines.push(match start {
+        Some(_) => "END:VEVENT".to_string(),
+        None => "END:VTODO".to_string(),
+    });
+    Some(lines)
+}
+
+#[derive(Clone, Copy)]
+enum Dated {
+    Day(NaiveDate),
+    Time(NaiveDateTime),
+}
+
+impl Dated {
+    fn format(&self) -> String {
+        match self {
+            Dated::Day(day) => day.format("%Y%m%d").to_string(),
+            Dated::Time(time) => time.date().format("%Y%m%d").to_string(),
+        }
+    }
+}
+
+/// `2024-05-01` or `2024-05-01T14:00`
+fn parse_date(value: &str) -> Option<Dated> {
+    if let Ok(time) = NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M") {
+        return Some(Dated::Time(time));
+    }
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .ok()
+        .map(Dated::Day)
+}
+
+/// Estimate like `2h`, `45m` or `1d4h` as an iCalendar duration
+fn parse_duration(value: &str) -> Option<String> {
+    let (mut days, mut hours, mut minutes) = (0, 0, 0);
+    let mut number = String::new();
+    for c in value.chars() {
+        match c {
+            '0'..='9' => number.push(c),
+            'd' | 'h' | 'm' => {
+                let n: u32 = number.parse().ok()?;
+                number.clear();
+                match c {
+                    'd' => days += n,
+                    'h' => hours += n,
+                    _ => minutes += n,
+                }
+            }
+            _ => return None,
+        }
+    }
+    if !number.is_empty() || days + hours + minutes == 0 {
+        return None;
+    }
+    let mut duration = String::from("P");
+    if days > 0 {
+        duration.push_str(&format!("{days}D"));
+    }
+    if hours + minutes > 0 {
+        duration.push('T');
+    }
+    if hours > 0 {
+        duration.push_str(&format!("{hours}H"));
+    }
+    if minutes > 0 {
+        duration.push_str(&format!("{minutes}M"));
+    }
+    Some(duration)
+}
+
+/// Escapes a TEXT value
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+/// Splits line in lines of at most 75 bytes, continuation lines start with a space
+fn fold(line: &str) -> String {
+    let mut out = String::new();
+    let mut length = 0;
+    for c in line.chars() {
+        if length + c.len_utf8() > 75 {
+            out.push_str("\r\n ");
+            length = 1;
+        }
+        out.push(c);
+        length += c.len_utf8();
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use indoc::indoc;
+
+    fn date(d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 3, d).unwrap()
+    }
+
+    #[test]
+    fn ics_export() {
+        let todo: Todo = indoc! {"
+            [2024-03-07]
+            Work
+            - [~] deploy, again #release due:2024-03-09
+              after the review
+            - standup at:09:30 est:15m
+            - review
+
+            [2024-03-06]
+            Work
+            - [ ] deploy, again #release due:2024-03-08
+            - [-] demo scheduled:2024-03-08T14:00
+        "}
+        .parse()
+        .unwrap();
+        let now = "2024-03-07T18:30:00Z".parse().unwrap();
+        let ics = to_string_at(&todo, now);
+        assert!(ics.ends_with("END:VCALENDAR\r\n"));
+        let lines: Vec<&str> = ics.lines().collect();
+
+        // newest copy of deploy wins
+        let deploy = uid(
+            date(7),
+            &Section::new("Work"),
+            &[],
+            &Task::new("deploy, again #release due:2024-03-09"),
+        );
+        let sequence = (date(7) - NaiveDate::default()).num_days();
+        assert_eq!(ics.matches(&deploy).count(), 1);
+        let start = lines
+            .iter()
+            .position(|line| *line == "BEGIN:VTODO")
+            .unwrap();
+        assert_eq!(
+            lines[start..start + 9],
+            [
+                "BEGIN:VTODO",
+                &format!("UID:{deploy}"),
+                "DTSTAMP:20240307T183000Z",
+                &format!("SEQUENCE:{sequence}"),
+                "DUE;VALUE=DATE:20240309",
+                "STATUS:IN-PROCESS",
+                "SUMMARY:deploy\\, again #release",
+                "DESCRIPTION:after the review",
+                "CATEGORIES:Work,release",
+            ]
+        );
+
+        assert!(ics.contains(
+            "DTSTART:20240307T093000\r\nDURATION:PT15M\r\nSTATUS:CONFIRMED\r\nSUMMARY:standup\r\n"
+        ));
+        assert!(ics.contains(
+            "DTSTART:20240308T140000\r\nDURATION:PT1H\r\nSTATUS:CANCELLED\r\nSUMMARY:demo"
+        ));
+        assert!(!ics.contains("SUMMARY:review"));
+    }
+
+    #[test]
+    fn ics_uid_from_id() {
+        let todo: Todo = indoc! {"
+            [2024-03-08]
+            Work
+            - deploy to production id:deploy due:2024-03-09
+
+            [2024-03-07]
+            Home
+            - deploy id:deploy due:2024-03-09
+
+            [2024-03-06]
+            Home
+            - deploy id:deploy due:2024-03-09
+        "}
+        .parse()
+        .unwrap();
+        let ics = to_string(&todo);
+
+        // edited text and moved section keep the entry
+        assert_eq!(ics.matches("BEGIN:VTODO").count(), 1);
+        assert!(ics.contains("SUMMARY:deploy to production\r\n"));
+    }
+
+    #[test]
+    fn ics_daily_events_and_subtasks() {
+        let todo: Todo = indoc! {"
+            [2024-03-07]
+            Work
+            - standup at:09:30
+            - deploy
+              - build due:2024-03-08
+            - release
+              - build due:2024-03-08
+
+            [2024-03-06]
+            Work
+            - standup at:09:30
+        "}
+        .parse()
+        .unwrap();
+        let ics = to_string(&todo);
+
+        // one event a day, one todo for each build
+        assert!(ics.contains("DTSTART:20240307T093000\r\n"));
+        assert!(ics.contains("DTSTART:20240306T093000\r\n"));
+        assert_eq!(ics.matches("BEGIN:VTODO").count(), 2);
+
+        // SEQUENCE follows the newest day, exporting fewer days doesn't lower it
+        let newest = |ics: &str| {
+            let start = ics.find("BEGIN:VTODO").unwrap();
+            ics[start..]
+                .lines()
+                .find(|line| line.starts_with("SEQUENCE"))
+                .unwrap()
+                .to_string()
+        };
+        let mut recent = todo.clone();
+        recent.days.remove(&date(6));
+        assert_eq!(newest(&to_string(&recent)), newest(&ics));
+    }
+
+    #[test]
+    fn ics_values() {
+        assert_eq!(parse_duration("1d4h30m").unwrap(), "P1DT4H30M");
+        assert_eq!(parse_duration("2h").unwrap(), "PT2H");
+        assert!(parse_duration("2 hours").is_none());
+        assert!(parse_duration("15").is_none());
+
+        let line = format!("SUMMARY:{}", "é".repeat(40));
+        let folded = fold(&line);
+        assert!(folded.split("\r\n").all(|line| line.len() <= 75));
+        assert_eq!(folded.replace("\r\n ", ""), line);
+    }
+}
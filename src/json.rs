@@ -0,0 +1,299 @@
+//! JSON documents of todos, needs the `serde` feature
+//!
+//! A todo is written as an object with the schema version and its days, newest first:
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "days": [
+//!     {
+//!       "date": "2024-03-07",
+//!       "planned": false,
+//!       "sections": [
+//!         {
+//!           "name": "Work",
+//!           "tasks": [
+//!             {
+//!               "text": "deploy #release due:2024-03-08",
+//!               "status": "in-progress",
+//!               "checkbox": true,
+//!               "tags": ["release"],
+//!               "contexts": [],
+//!               "properties": { "due": "2024-03-08" },
+//!               "indent": "",
+//!               "subtasks": [],
+//!               "notes": [{ "text": "after the review", "indent": "  ", "position": 0 }]
+//!             }
+//!           ]
+//!         }
+//!       ]
+//!     }
+//!   ]
+//! }
+//! ```
+//!
+//! - `status` is one of `open`, `done`, `in-progress`, `blocked`, `cancelled` and `deferred`
+//! - tasks of anonymous sections are in a section named `""`
+//! - `tags`, `contexts` and `properties` are read from `text`, they are ignored when importing
+//! - only `date`, `name` and `text` are required when importing, subtasks and notes are indented
+//!   below their parent if their `indent` isn't deeper
+//! - notes may be imported as strings, `position` is the number of subtasks above the note
+//! - text and names must fit on one line and read back the same: they can't start or end with
+//!   whitespace, task text can't start with a checkbox or a dash, notes can't start with a dash,
+//!   section names can't start with `[` or `-` and only the first section of a day can be anonymous
+
+use crate::{
+    err,
+    task::{indent_width, parse_checkbox, Task},
+    util::Result,
+    Day, Error, Todo,
+};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Version of the schema written to and accepted in documents
+pub const VERSION: u32 = 1;
+
+/// What a todo is serialized as
+#[derive(Serialize, Deserialize)]
+pub struct Document {
+    pub version: u32,
+    pub days: Vec<Day>,
+}
+
+impl From<Todo> for Document {
+    fn from(todo: Todo) -> Self {
+        let days = todo
+            .days
+            .into_values()
+            .sorted_by(|a, b| b.date.cmp(&a.date))
+            .collect();
+        Document {
+            version: VERSION,
+            days,
+        }
+    }
+}
+
+impl TryFrom<Document> for Todo {
+    type Error = Error;
+    fn try_from(document: Document) -> Result<Todo> {
+        if document.version != VERSION {
+            return err!(
+                "Unsupported JSON version {}, expected {VERSION}",
+                document.version
+            );
+        }
+        let mut days = HashMap::new();
+        for mut day in document.days {
+            for (index, section) in day.sections.iter_mut().enumerate() {
+                let name = &section.name;
+                // anonymous sections after the first one would be read back as part of the one above
+                if is_multiline(name)
+                    || is_padded(name)
+                    || name.starts_with(['[', '-'])
+                    || (name.is_empty() && index > 0)
+                {
+                    return err!(
+                        "Invalid JSON: section name {:?} can't be read back",
+                        section.name
+                    );
+                }
+                for task in &mut section.tasks {
+                    normalize(task)?;
+                }
+            }
+            if days.contains_key(&day.date) {
+                return err!("Invalid JSON: date {} appears more than once", day.date);
+            }
+            days.insert(day.date, day);
+        }
+        Ok(Todo {
+            days,
+            ..Default::default()
+        })
+    }
+}
+
+/// Reads metadata from the text of task and its subtasks and indents notes and subtasks below it,
+/// fails on text that would be read back as something else
+fn normalize(task: &mut Task) -> Result<()> {
+    if is_multiline(&task.text)
+        || is_padded(&task.text)
+        || task.text.starts_with('-')
+        || parse_checkbox(&task.text).is_some()
+    {
+        return err!("Invalid JSON: task text {:?} can't be read back", task.text);
+    }
+    task.set_text(&task.text.clone());
+    let width = indent_width(&task.indent);
+    for note in &mut task.notes {
+        if is_multiline(&note.text) || is_padded(&note.text) || note.text.starts_with('-') {
+            return err!("Invalid JSON: note {:?} can't be read back", note.text);
+        }
+        if !note.text.is_empty() && indent_width(&note.indent) <= width {
+            note.indent = format!("{}  ", task.indent);
+        }
+    }
+    for subtask in &mut task.subtasks {
+        if indent_width(&subtask.indent) <= width {
+            subtask.set_indent(&format!("{}  ", task.indent));
+        }
+        normalize(subtask)?;
+    }
+    Ok(())
+}
+
+fn is_multiline(text: &str) -> bool {
+    text.contains(['\n', '\r'])
+}
+
+/// Whether text starts or ends with whitespace, which is trimmed when reading it back
+fn is_padded(text: &str) -> bool {
+    text.trim() != text
+}
+
+/// Pretty printed JSON document of todo
+pub fn to_string(todo: &Todo) -> Result<String> {
+    match serde_json::to_string_pretty(todo) {
+        Ok(json) => Ok(json),
+        Err(e) => err!("Unable to write JSON: {e}"),
+    }
+}
+
+/// Todo in a JSON document, its days can be written to a file with [Todo::save]
+pub fn from_str(s: &str) -> Result<Todo> {
+    match serde_json::from_str(s) {
+        Ok(todo) => Ok(todo),
+        Err(e) => err!("Invalid JSON: {e}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::Status;
+    use indoc::indoc;
+
+    fn todo() -> Todo {
+        indoc! {"
+            [2024-03-07]
+            Work
+            - [~] deploy #release due:2024-03-08
+              after the review
+              - [x] build
+            - review
+
+            [2024-03-06]
+            - [x] groceries
+        "}
+        .parse()
+        .unwrap()
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let json = to_string(&todo()).unwrap();
+        let document: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(document["version"], 1);
+        assert_eq!(document["days"][0]["date"], "2024-03-07");
+        let task = &document["days"][0]["sections"][0]["tasks"][0];
+        assert_eq!(task["status"], "in-progress");
+        assert_eq!(task["properties"]["due"], "2024-03-08");
+        assert_eq!(task["subtasks"][0]["status"], "done");
+
+        assert_eq!(from_str(&json).unwrap(), todo());
+    }
+
+    #[test]
+    fn import_minimal_json() {
+        let json = r#"{
+            "version": 1,
+            "days": [{
+                "date": "2024-03-07",
+                "sections": [{
+                    "name": "Work",
+                    "tasks": [{"text": "deploy #release", "status": "done", "checkbox": true,
+                               "subtasks": [{"text": "build"}], "notes": ["after the review"]}]
+                }]
+            }]
+        }"#;
+        let todo = from_str(json).unwrap();
+        let task = &todo.days.values().next().unwrap().sections[0].tasks[0];
+        assert_eq!(task.status, Status::Done);
+        assert_eq!(task.tags, vec!["release"]);
+        assert_eq!(task.subtasks[0].indent, "  ");
+        assert_eq!(
+            todo.to_string(),
+            "[2024-03-07]\nWork\n- [x] deploy #release\n  after the review\n  - build"
+        );
+    }
+
+    #[test]
+    fn err_import_json() {
+        let day = r#"{"date": "2024-03-07"}"#;
+        for json in [
+            "[]".to_string(),
+            format!(r#"{{"version": 2, "days": [{day}]}}"#),
+            format!(r#"{{"version": 1, "days": [{day}, {day}]}}"#),
+            r#"{"version": 1, "days": [{"date": "2024-13-07"}]}"#.to_string(),
+        ] {
+            assert!(from_str(&json).is_err(), "{json}");
+        }
+
+        // text that would be read back as something else
+        for (name, task) in [
+            ("Work", r#"{"text": "deploy\n[2024-03-08]"}"#),
+            ("Work", r#"{"text": "[x] deploy"}"#),
+            ("Work", r#"{"text": "deploy", "notes": ["- build"]}"#),
+            (
+                "Work",
+                r#"{"text": "deploy", "subtasks": [{"text": "[ ] build"}]}"#,
+            ),
+            ("[2024-03-08]", r#"{"text": "deploy"}"#),
+            ("- Work", r#"{"text": "deploy"}"#),
+            (r"Work\nHome", r#"{"text": "deploy"}"#),
+            (" Indented", r#"{"text": "deploy"}"#),
+            ("Work ", r#"{"text": "deploy"}"#),
+            ("Work", r#"{"text": "-5 degrees"}"#),
+            ("Work", r#"{"text": "  spaced  "}"#),
+            (
+                "Work",
+                r#"{"text": "deploy", "notes": [" after the review"]}"#,
+            ),
+        ] {
+            let json = format!(
+                r#"{{"version": 1, "days": [{{"date": "2024-03-07", "sections": [{{"name": "{name}", "tasks": [{task}]}}]}}]}}"#
+            );
+            let err = from_str(&json).unwrap_err().to_string();
+            assert!(err.contains("can't be read back"), "{json}: {err}");
+        }
+        // only the first section can be anonymous
+        let sections = |names: [&str; 2]| {
+            let sections = names
+                .map(|name| format!(r#"{{"name": "{name}", "tasks": [{{"text": "deploy"}}]}}"#))
+                .join(", ");
+            format!(
+                r#"{{"version": 1, "days": [{{"date": "2024-03-07", "sections": [{sections}]}}]}}"#
+            )
+        };
+        assert!(from_str(&sections(["", "Work"])).is_ok());
+        let err = from_str(&sections(["Work", ""])).unwrap_err().to_string();
+        assert!(err.contains("can't be read back"), "{err}");
+    }
+
+    #[test]
+    fn import_json_tab_indent() {
+        // a tab is deeper than two spaces
+        let json = r#"{"version": 1, "days": [{"date": "2024-03-07", "sections": [{"name": "Work",
+            "tasks": [{"text": "deploy", "indent": "  ", "subtasks": [{"text": "build", "indent": "\t"}],
+                       "notes": [{"text": "after the review", "indent": "\t", "position": 1}]}]}]}]}"#;
+        let todo = from_str(json).unwrap();
+        assert_eq!(
+            todo.to_string(),
+            "[2024-03-07]\nWork\n  - deploy\n\t- build\n\tafter the review"
+        );
+    }
+}
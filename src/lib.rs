@@ -1,159 +1,489 @@
-//! Todo is NOT timezone-aware
+//! Days follow the timezone and day start hour in [Config], the system's timezone and midnight by
+//! default
 
 use chrono::NaiveDate;
-use section::Section;
 use std::{
-    collections::HashMap, error, fmt, fs::read_to_string, fs::OpenOptions, io::Write,
-    path::PathBuf, str,
+    collections::HashMap, fmt, fs::read_to_string, fs::OpenOptions, io::Write, path::PathBuf, str,
+    sync::Arc,
 };
 
+pub mod address;
+pub mod clock;
+pub mod config;
+pub mod cst;
 mod day;
+mod error;
+pub mod filter;
+pub mod format;
+pub mod html;
+pub mod ics;
+#[cfg(feature = "serde")]
+pub mod json;
+pub mod lint;
+pub mod markdown;
+pub mod query;
+pub mod rollover;
+pub mod search;
 mod section;
 mod task;
+pub mod todotxt;
 mod util;
 
-pub use util::today;
-use util::*;
+pub use util::Result;
 
+use address::{content_hash, positions, task_at_mut, Address};
+use clock::{Clock, SystemClock};
+use config::Config;
+use cst::SyntaxTree;
 pub use day::{Day, DayIterator};
-use task::Task;
+pub use error::{Error, Location};
+use itertools::Itertools;
+use query::Query;
+use rollover::Rollover;
+use search::{Pattern, SearchIterator};
+pub use section::{Section, SectionIterator};
+pub use task::{Note, Status, Task};
 
-#[derive(PartialEq, Debug, Clone)]
+/// Date in the system timezone
+#[deprecated(note = "use Todo::today, which follows the clock and config of the todo")]
+pub fn today() -> NaiveDate {
+    SystemClock.today(&Config::default())
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(into = "json::Document", try_from = "json::Document")
+)]
 pub struct Todo {
     pub days: HashMap<NaiveDate, Day>,
     pub file_path: PathBuf,
+    /// Settings used to read, write and roll over the file
+    pub config: Config,
+    /// Syntax tree of the file the todo was read from, used to save it without reformatting
+    tree: Option<SyntaxTree>,
+    /// Where today's date comes from
+    clock: Arc<dyn Clock>,
+}
+
+impl Default for Todo {
+    fn default() -> Self {
+        Todo {
+            days: HashMap::new(),
+            file_path: PathBuf::new(),
+            config: Config::default(),
+            tree: None,
+            clock: Arc::new(SystemClock),
+        }
+    }
+}
+
+// the syntax tree is only a record of how days were written, todos with the same days are equal
+impl PartialEq for Todo {
+    fn eq(&self, other: &Self) -> bool {
+        self.days == other.days && self.file_path == other.file_path
+    }
 }
 
 impl Todo {
     pub fn new(path: Option<&str>) -> Result<Todo> {
+        Todo::new_with(path, &Config::default())
+    }
+
+    /// Opens the todo file at path, creating it if it doesn't exist, or creates the file configured as default
+    pub fn new_with(path: Option<&str>, config: &Config) -> Result<Todo> {
         let path: PathBuf = match path {
             // if path present but file doesnt exist, create it
             Some(path) => {
                 if let Err(_error) = OpenOptions::new().read(true).open(path) {
-                    OpenOptions::new().write(true).create(true).open(path)?;
+                    OpenOptions::new()
+                        .write(true)
+                        .create(true)
+                        .truncate(false)
+                        .open(path)
+                        .map_err(|e| Error::io(path, e))?;
                 };
                 path.into()
             }
             // if path not present, create default file if possible
             None => {
-                if let Err(_error) = OpenOptions::new()
-                    .write(true)
-                    .create_new(true)
-                    .open(DEFAULT_TODO_FILE)
-                {
-                    return err!("File with default name {DEFAULT_TODO_FILE} already exists");
+                let path = &config.file;
+                if let Err(_error) = OpenOptions::new().write(true).create_new(true).open(path) {
+                    return err!("File with default name {} already exists", path.display());
                 }
-                DEFAULT_TODO_FILE.into()
+                path.clone()
             }
         };
 
         // load from file or create new blank one
-        let todo = Todo::load(&path).unwrap_or(Todo {
-            days: HashMap::<NaiveDate, Day>::new(),
+        let todo = Todo::load_with(&path, config).unwrap_or(Todo {
             file_path: path,
+            config: config.clone(),
+            ..Default::default()
         });
 
         Ok(todo)
     }
 
-    fn last_day(&self) -> Option<&Day> {
-        if self.days.len() == 0 {
-            return None;
+    /// Syntax tree of the file the todo was read from, if any
+    pub fn syntax_tree(&self) -> Option<&SyntaxTree> {
+        self.tree.as_ref()
+    }
+
+    /// Number of tasks in all days with the given status
+    pub fn count(&self, status: Status) -> usize {
+        self.days.values().map(|day| day.count(status)).sum()
+    }
+
+    /// Number of tasks in all days for each status
+    pub fn count_by_status(&self) -> HashMap<Status, usize> {
+        let mut counts = HashMap::new();
+        for day in self.days.values() {
+            for (status, count) in day.count_by_status() {
+                *counts.entry(status).or_insert(0) += count;
+            }
         }
+        counts
+    }
 
-        self.days.values().max_by_key(|x| x.date)
+    /// Iterates over every task (and subtask) with the date and section it is in, newest days first
+    pub fn iter_tasks(&self) -> impl Iterator<Item = (NaiveDate, &Section, &Task)> + '_ {
+        self.days
+            .values()
+            .sorted_by(|a, b| b.date.cmp(&a.date))
+            .flat_map(|day| {
+                day.sections.iter().flat_map(move |section| {
+                    section
+                        .iter_tasks()
+                        .map(move |task| (day.date, section, task))
+                })
+            })
     }
 
-    /// Creates new empty day if there isn't a day with today() as date.
-    /// ensure_today is idempotent, meaning it will do nothing if today already exists in days
-    fn ensure_today(&mut self) {
-        let new_day = match self.last_day() {
-            Some(last_day) => {
-                // days and today is created: do nothing
-                if last_day.date == today() {
-                    return;
+    /// Tasks matching query, see [query] for its syntax
+    pub fn query(&self, query: &str) -> Result<Vec<(NaiveDate, &Section, &Task)>> {
+        let query = Query::parse(query, self.today())?;
+        let tasks = self
+            .iter_tasks()
+            .filter(|(date, section, task)| query.matches(*date, section, task));
+        Ok(tasks.collect())
+    }
+
+    /// Tasks whose text or notes match pattern, newest days first
+    pub fn search<'a>(
+        &'a self,
+        pattern: &'a Pattern,
+    ) -> SearchIterator<'a, impl Iterator<Item = (NaiveDate, &'a Section, &'a Task)>> {
+        SearchIterator::new(self.iter_tasks(), pattern)
+    }
+
+    /// Every task (and subtask) with its position address, newest days first
+    ///
+    /// When a day has two sections with the same name, tasks of the second one can't be told apart
+    /// from those of the first one by position.
+    pub fn addresses(&self) -> Vec<(Address, &Section, &Task)> {
+        let mut addresses = Vec::new();
+        for day in self.days.values().sorted_by(|a, b| b.date.cmp(&a.date)) {
+            for section in &day.sections {
+                for (position, task) in positions(&section.tasks) {
+                    let address = Address::Position {
+                        date: day.date,
+                        section: section.name.clone(),
+                        position,
+                    };
+                    addresses.push((address, section, task));
                 }
-                // days but no today: copy last day
-                let mut clone_last_day = last_day.clone();
-                clone_last_day.date = today();
-                clone_last_day
             }
-            // no days: create new empty day
-            None => Day::new(today()),
+        }
+        addresses
+    }
+
+    /// Position address of the task address points at, fails unless exactly one task matches
+    pub fn resolve(&self, address: &Address) -> Result<Address> {
+        let addresses = self.addresses();
+        let found: Vec<&Address> = addresses
+            .iter()
+            .filter(|(position, section, _)| match (address, position) {
+                (Address::Hash(hash), Address::Position { date, position, .. }) => {
+                    content_hash(*date, section, position).starts_with(hash.as_str())
+                }
+                _ => position == address,
+            })
+            .map(|(position, _, _)| position)
+            .collect();
+        match found[..] {
+            [] => err!("Unable to find task {address}"),
+            [position] => Ok(position.clone()),
+            _ => err!("Task address {address} matches more than one task"),
+        }
+    }
+
+    pub fn task(&self, address: &Address) -> Result<&Task> {
+        let address = self.resolve(address)?;
+        let found = self.addresses().into_iter().find(|(a, _, _)| *a == address);
+        match found {
+            Some((_, _, task)) => Ok(task),
+            None => err!("Unable to find task {address}"),
+        }
+    }
+
+    pub fn task_mut(&mut self, address: &Address) -> Result<&mut Task> {
+        let (section, position) = self.locate_mut(address)?;
+        match task_at_mut(&mut section.tasks, &position) {
+            Some(task) => Ok(task),
+            None => err!("Unable to find task {address}"),
+        }
+    }
+
+    /// Section the task address points at is in and its position in the section
+    fn locate_mut(&mut self, address: &Address) -> Result<(&mut Section, Vec<usize>)> {
+        let Address::Position {
+            date,
+            section,
+            position,
+        } = self.resolve(address)?
+        else {
+            return err!("Unable to find task {address}");
         };
-        self.days.insert(new_day.date, new_day);
+        let found = self
+            .days
+            .get_mut(&date)
+            .and_then(|day| day.sections.iter_mut().find(|s| s.name == section));
+        match found {
+            Some(section) => Ok((section, position)),
+            None => err!("Unable to find task {address}"),
+        }
+    }
+
+    /// Most recent day before date that is not planned
+    fn last_day_before(&self, date: NaiveDate) -> Option<&Day> {
+        self.days
+            .values()
+            .filter(|day| day.date < date && !day.planned)
+            .max_by_key(|day| day.date)
     }
 
-    /// Creates new day with all tasks/sections from most recent day and cleared Done section
+    /// Creates today from the last day following the rollover policy if there isn't a day with today's date, see [Todo::today].
+    /// Planned days up to today are merged into it.
+    /// ensure_today is idempotent, meaning it will do nothing if today already exists in days and isn't planned
+    fn ensure_today(&mut self) {
+        let rollover = self.config.rollover.clone();
+        self.roll_over(&rollover);
+    }
+
+    /// Creates new day with all tasks/sections from most recent day and cleared done section
     /// next_day is idempotent, meaning it will do nothing if today already exists in days
-    #[deprecated]
+    #[deprecated(note = "set a rollover policy that clears Done instead")]
     pub fn next_day(&mut self) {
-        let mut new_day = match self.last_day() {
-            Some(last_day) => {
-                // days and today is created: do nothing
-                if last_day.date == today() {
-                    return;
-                }
-                // days but no today: copy last day
-                let mut clone_last_day = last_day.clone();
-                clone_last_day.date = today();
-                clone_last_day
-            }
-            // no days: create new empty day
-            None => Day::new(today()),
-        };
+        let done = vec![self.config.done_section.clone()];
+        self.roll_over(&Rollover {
+            clear: done.clone(),
+            template: done,
+            ..Default::default()
+        });
+    }
 
-        // clear "Done" section, create one if didnt find
-        match new_day
-            .sections
-            .iter()
-            .position(|section| section.name == "Done")
-        {
-            Some(pos) => {
-                new_day.sections[pos].tasks = Vec::<Task>::new();
-            }
-            None => {
-                let sec = Section::new("Done");
-                new_day.sections.push(sec);
-            }
+    fn roll_over(&mut self, rollover: &Rollover) {
+        // today is created: do nothing
+        let today = self.today();
+        if self.days.get(&today).is_some_and(|day| !day.planned) {
+            return;
         }
+        // today may be in the past with a fixed clock, it starts from the day before it
+        let last_day = self.last_day_before(today);
+        let mut new_day = rollover.roll(last_day, today);
 
+        // tasks planned for today, or for days that went by without a rollover, are added after the carried ones
+        let planned: Vec<NaiveDate> = self
+            .days
+            .values()
+            .filter(|day| day.planned && day.date <= today)
+            .map(|day| day.date)
+            .sorted()
+            .collect();
+        for date in planned {
+            let Some(day) = self.days.remove(&date) else {
+                continue;
+            };
+            for section in day.sections {
+                new_day
+                    .section_mut(&section.name)
+                    .tasks
+                    .extend(section.tasks);
+            }
+        }
         self.days.insert(new_day.date, new_day);
     }
 
-    pub fn save(&mut self) -> Result<()> {
-        if let Some(last_day_in_file) = get_last_day(&self.file_path) {
-            if last_day_in_file > today() {
-                return err!("Invalid date: date on file is ahead of today");
+    /// Adds task to section of the day with date, which must be after today, creating the day as planned
+    pub fn plan(&mut self, date: NaiveDate, task_txt: &str, section: &str) -> Result<()> {
+        if date <= self.today() {
+            return err!("Unable to plan for {date}, only days after today can be planned");
+        }
+        let task: Task = task_txt.parse()?;
+        let day = self.days.entry(date).or_insert_with(|| Day::new(date));
+        day.planned = true;
+        day.section_mut(section).tasks.push(task);
+        Ok(())
+    }
+
+    /// Adds task to section of the day with date, which must be before today, creating the day empty.
+    /// Nothing is rolled over into a past day, it only gets the tasks added to it
+    pub fn backfill(&mut self, date: NaiveDate, task_txt: &str, section: &str) -> Result<()> {
+        if date >= self.today() {
+            return err!("Unable to backfill {date}, only days before today can be backfilled");
+        }
+        let task: Task = task_txt.parse()?;
+        let day = self.days.entry(date).or_insert_with(|| Day::new(date));
+        day.section_mut(section).tasks.push(task);
+        Ok(())
+    }
+
+    /// Adds the days of other, tasks of days that already exist are added to the end of their
+    /// section unless it has a task with the same text
+    pub fn merge(&mut self, other: Todo) {
+        for (date, day) in other.days {
+            let Some(existing) = self.days.get_mut(&date) else {
+                self.days.insert(date, day);
+                continue;
+            };
+            for section in day.sections {
+                let existing = existing.section_mut(&section.name);
+                for task in section.tasks {
+                    if !existing.tasks.iter().any(|t| t.text == task.text) {
+                        existing.tasks.push(task);
+                    }
+                }
             }
         }
+    }
+
+    /// Days after today, soonest first
+    pub fn upcoming(&self) -> Vec<&Day> {
+        let today = self.today();
+        self.days
+            .values()
+            .filter(|day| day.date > today)
+            .sorted_by_key(|day| day.date)
+            .collect()
+    }
 
+    /// Date of the day that is going on, following the clock and the timezone and day start in config
+    pub fn today(&self) -> NaiveDate {
+        self.clock.today(&self.config)
+    }
+
+    /// Uses clock instead of the system clock to know what day it is
+    pub fn set_clock(&mut self, clock: impl Clock + 'static) {
+        self.clock = Arc::new(clock);
+        self.plan_future_days();
+    }
+
+    /// Marks days after today as planned, so days written ahead without the planned marker are
+    /// merged in at rollover too
+    fn plan_future_days(&mut self) {
+        let today = self.today();
+        for day in self.days.values_mut().filter(|day| day.date > today) {
+            day.planned = true;
+        }
+    }
+
+    pub fn save(&mut self) -> Result<()> {
         // don't save if file is up to date
-        let file_todo = Todo::load(&self.file_path)?;
+        let file_todo = Todo::load_with(&self.file_path, &self.config)?;
         if file_todo == *self {
-            return err!("File already up to date");
+            return Err(Error::UpToDate {
+                path: self.file_path.clone(),
+            });
+        }
+
+        // only rewrite what changed since the file was read
+        let contents = match &self.tree {
+            Some(tree) => tree.render(&self.days),
+            None => format!("{self}\n"),
+        };
+
+        self.write(&contents)
+    }
+
+    /// Rewrites the file in canonical form, see [format::format]
+    pub fn format(&mut self) -> Result<()> {
+        let contents = format::format(self)?;
+        let unchanged = self
+            .tree
+            .as_ref()
+            .is_some_and(|tree| tree.source() == contents);
+        if unchanged {
+            return Err(Error::UpToDate {
+                path: self.file_path.clone(),
+            });
         }
+        self.write(&contents)
+    }
 
-        let mut f = OpenOptions::new()
+    fn write(&mut self, contents: &str) -> Result<()> {
+        OpenOptions::new()
             .write(true)
             .truncate(true)
-            .open(&self.file_path)?;
-        f.write_all(format!("{self}\n").as_bytes())?;
+            .open(&self.file_path)
+            .and_then(|mut f| f.write_all(contents.as_bytes()))
+            .map_err(|e| Error::io(&self.file_path, e))?;
+        self.tree = Some(SyntaxTree::parse_with(contents, &self.config.date_format)?);
         Ok(())
     }
 
     pub fn load(todo_file: &PathBuf) -> Result<Todo> {
-        if let Some(last_day) = get_last_day(todo_file) {
-            if last_day > today() {
-                return err!("Invalid date: date on file is ahead of today");
-            }
+        Todo::load_with(todo_file, &Config::default())
+    }
+
+    pub fn load_with(todo_file: &PathBuf, config: &Config) -> Result<Todo> {
+        let (todo, errors) = Todo::load_lenient_with(todo_file, config)?;
+        match errors.into_iter().next() {
+            Some(e) => Err(e),
+            None => Ok(todo),
         }
-        let mut todo: Todo = read_to_string(&todo_file)
-            .expect("Unable to read file")
-            .parse()
-            .expect("Unable to parse file contents");
+    }
+
+    /// Loads as much of the file as possible and returns every problem found in it
+    ///
+    /// Only fails if the file can't be read
+    pub fn load_lenient(todo_file: &PathBuf) -> Result<(Todo, Vec<Error>)> {
+        Todo::load_lenient_with(todo_file, &Config::default())
+    }
+
+    /// Same as [Todo::load_lenient] with the given settings, a rollover policy next to the file overrides the one in config
+    /// but not [Config::overrides]
+    pub fn load_lenient_with(todo_file: &PathBuf, config: &Config) -> Result<(Todo, Vec<Error>)> {
+        let contents = read_to_string(todo_file).map_err(|e| Error::io(todo_file, e))?;
+        let (mut todo, mut errors) = Todo::parse_lenient_with(&contents, config);
+
+        errors.sort_by_key(|e| e.location().map(|location| location.line));
+
+        let errors = errors.into_iter().map(|e| e.with_path(todo_file)).collect();
         todo.file_path = todo_file.to_path_buf();
-        Ok(todo)
+        if let Some(rollover) = Rollover::for_file(todo_file)? {
+            todo.config.set_rollover(rollover);
+        }
+        todo.plan_future_days();
+        Ok((todo, errors))
+    }
+
+    /// Parses as much of s as possible, skipping lines and days that can't be parsed, and returns every problem found
+    pub fn parse_lenient(s: &str) -> (Todo, Vec<Error>) {
+        Todo::parse_lenient_with(s, &Config::default())
+    }
+
+    /// Same as [Todo::parse_lenient] with the given settings
+    pub fn parse_lenient_with(s: &str, config: &Config) -> (Todo, Vec<Error>) {
+        let (tree, errors) = SyntaxTree::parse_lenient_with(s, &config.date_format);
+        let todo = Todo {
+            days: tree.days(),
+            tree: Some(tree),
+            config: config.clone(),
+            ..Default::default()
+        };
+        (todo, errors)
     }
 
     pub fn add(&mut self, task_txt: &str, section: &str) -> Result<()> {
@@ -162,46 +492,99 @@ impl Todo {
 
         let task: Task = task_txt.parse()?;
 
-        if let Some(day) = self.days.get_mut(&today()) {
-            // find section position in vec
-            let sections = &mut day.sections;
-
-            let section_pos = sections
-                .iter()
-                .position(|sec| sec.name == section)
-                // create section if it doesnt exist
-                .unwrap_or_else(|| {
-                    sections.push(Section::new(section));
-                    sections.len() - 1
-                });
-
-            // put task in section
-            day.sections[section_pos].tasks.push(task);
+        if let Some(day) = self.days.get_mut(&self.today()) {
+            // create section if it doesnt exist
+            day.section_mut(section).tasks.push(task);
         }
         Ok(())
     }
-}
 
-impl str::FromStr for Todo {
-    type Err = Box<dyn error::Error + Send + Sync>;
-    fn from_str(s: &str) -> Result<Self> {
-        let text = s.trim().to_string();
-        let mut days: HashMap<NaiveDate, Day> = HashMap::new();
-        let day_iter = DayIterator::new(&text);
+    /// Replaces the text of the task address points at, keeping its status
+    pub fn edit(&mut self, address: &Address, text: &str) -> Result<()> {
+        // accept both "- task" and "task"
+        let text = match text.parse::<Task>() {
+            Ok(task) => task.text,
+            Err(_) => text.trim().to_string(),
+        };
+        self.task_mut(address)?.set_text(&text);
+        Ok(())
+    }
+
+    /// Removes the task address points at, with its subtasks
+    pub fn delete(&mut self, address: &Address) -> Result<Task> {
+        let (section, position) = self.locate_mut(address)?;
+        match section.remove_task(&position) {
+            Some(task) => Ok(task),
+            None => err!("Unable to find task {address}"),
+        }
+    }
+
+    /// Moves the task address points at to the end of another section of the same day, creating it if needed
+    pub fn move_task(&mut self, address: &Address, section: &str) -> Result<()> {
+        let Address::Position { date, .. } = self.resolve(address)? else {
+            return err!("Unable to find task {address}");
+        };
+        let mut task = self.delete(address)?;
+        task.set_indent("");
+        if let Some(day) = self.days.get_mut(&date) {
+            day.section_mut(section).tasks.push(task);
+        }
+        Ok(())
+    }
+
+    /// Moves the task address points at to another place among the tasks next to it, starting at 1
+    pub fn reorder(&mut self, address: &Address, to: usize) -> Result<()> {
+        let (section, position) = self.locate_mut(address)?;
+        match section.reorder_task(&position, to) {
+            true => Ok(()),
+            false => err!("Unable to find task {address}"),
+        }
+    }
+
+    /// Marks the first unfinished task matching task_txt in today's section as done
+    pub fn complete(&mut self, task_txt: &str, section: &str) -> Result<()> {
+        // make sure current day exists
+        self.ensure_today();
 
-        let old_date = NaiveDate::from_ymd_opt(1970, 1, 1).unwrap();
-        let mut last_day = Day::new(old_date); // set date to old date
+        // accept both "- task" and "task"
+        let text = match task_txt.parse::<Task>() {
+            Ok(task) => task.text,
+            Err(_) => task_txt.trim().to_string(),
+        };
 
-        for day in day_iter {
-            if day.date > last_day.date {
-                last_day = day.clone();
+        let Some(section) = self
+            .days
+            .get_mut(&self.today())
+            .and_then(|day| day.sections.iter_mut().find(|sec| sec.name == section))
+        else {
+            return err!("Unable to find section {section}");
+        };
+
+        match section.find_open_task_mut(&text) {
+            Some(task) => task.complete(),
+            None => {
+                return err!(
+                    "Unable to find open task in section {}: {text}",
+                    section.name
+                )
             }
-            days.insert(day.date, day);
         }
+        // parents of the completed task may be done now
+        section.roll_up();
+        Ok(())
+    }
+}
+
+impl str::FromStr for Todo {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self> {
+        let tree = SyntaxTree::parse(s)?;
 
         Ok(Todo {
-            days,
+            days: tree.days(),
             file_path: PathBuf::new(), // no path to give, is this an issue?
+            tree: Some(tree),
+            ..Default::default()
         })
     }
 }
@@ -213,7 +596,8 @@ impl fmt::Display for Todo {
 
         let sorted_str_days: Vec<String> = dates
             .iter()
-            .filter_map(|date| self.days.get(date).map(|day| day.to_string()))
+            .filter_map(|date| self.days.get(date))
+            .map(|day| day.render(&self.config.date_format))
             .collect();
 
         write!(f, "{}", sorted_str_days.join("\n"))
@@ -224,15 +608,22 @@ impl fmt::Display for Todo {
 #[allow(deprecated)]
 mod tests {
     use super::*;
+    use crate::clock::FixedClock;
     use crate::section::Section;
     use crate::task::Task;
 
     use chrono::Duration as ChronoDuration;
     use chrono::NaiveDate;
     use indoc::indoc;
+    use itertools::Itertools;
     use std::io::Write;
     use tempfile::NamedTempFile;
 
+    /// Date tests run on, todos get it with a [FixedClock]
+    fn today() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 3, 8).unwrap()
+    }
+
     /// Creates a tmp file with string contents and return the file path
     fn create_file_with_contents(contents: String) -> NamedTempFile {
         let mut file = NamedTempFile::new().expect("Unable to create tmp file");
@@ -269,19 +660,14 @@ mod tests {
                     NaiveDate::from_ymd_opt(2024, 3, 6).unwrap(),
                     Day {
                         date: NaiveDate::from_ymd_opt(2024, 3, 6).unwrap(),
+                        planned: false,
                         sections: vec![
                             Section {
                                 name: "Section 1".to_string(),
                                 tasks: vec![
-                                    Task {
-                                        text: "task 1".to_string(),
-                                    },
-                                    Task {
-                                        text: "task 3".to_string(),
-                                    },
-                                    Task {
-                                        text: "task 2".to_string(),
-                                    },
+                                    Task::new("task 1"),
+                                    Task::new("task 3"),
+                                    Task::new("task 2"),
                                 ],
                             },
                             Section {
@@ -295,38 +681,111 @@ mod tests {
                     NaiveDate::from_ymd_opt(2024, 3, 7).unwrap(),
                     Day {
                         date: NaiveDate::from_ymd_opt(2024, 3, 7).unwrap(),
+                        planned: false,
                         sections: vec![
                             Section {
                                 name: "Section 2".to_string(),
                                 tasks: vec![
-                                    Task {
-                                        text: "task 11".to_string(),
-                                    },
-                                    Task {
-                                        text: "task 31".to_string(),
-                                    },
-                                    Task {
-                                        text: "task 21".to_string(),
-                                    },
+                                    Task::new("task 11"),
+                                    Task::new("task 31"),
+                                    Task::new("task 21"),
                                 ],
                             },
                             Section {
                                 name: "Done".to_string(),
-                                tasks: vec![Task {
-                                    text: "task 4".to_string(),
-                                }],
+                                tasks: vec![Task::new("task 4")],
                             },
                         ],
                     },
                 ),
             ]),
             file_path: path.clone(),
+            ..Default::default()
         };
 
         let actual = Todo::load(&path).expect("Unable to load file");
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn err_load_todo() {
+        let file = create_file_with_contents(
+            indoc! {"
+            [2024-03-07]
+            Section 1
+            - task 1
+
+            [2024-03-32]
+            - task 2
+        "}
+            .to_string(),
+        );
+        let path = file.path().to_path_buf();
+
+        match Todo::load(&path) {
+            Err(Error::InvalidDateHeader { header, location }) => {
+                assert_eq!(header, "[2024-03-32]");
+                assert_eq!(location.path, Some(path));
+                assert_eq!((location.line, location.column), (5, 1));
+            }
+            other => panic!("Expected invalid date header error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn load_future_day() {
+        let tomorrow = today() + ChronoDuration::days(1);
+        let file = create_file_with_contents(format!(
+            "{} (planned)\n- task\n[2024-03-07]\n- task\n",
+            tomorrow.format("[%Y-%m-%d]")
+        ));
+
+        let mut todo = Todo::load(&file.path().to_path_buf()).unwrap();
+        todo.set_clock(FixedClock(today()));
+        assert!(todo.days[&tomorrow].planned);
+        let upcoming: Vec<NaiveDate> = todo.upcoming().iter().map(|day| day.date).collect();
+        assert_eq!(upcoming, vec![tomorrow]);
+    }
+
+    #[test]
+    fn load_lenient() {
+        let file = create_file_with_contents(
+            indoc! {"
+            [2024-03-07]
+            Section 1
+              stray note
+            - task 1
+
+            [2024-03-32]
+            - task 2
+
+            [2024-03-05]
+            - task 3
+        "}
+            .to_string(),
+        );
+        let path = file.path().to_path_buf();
+
+        let (todo, errors) = Todo::load_lenient(&path).expect("Unable to read file");
+        let dates: Vec<NaiveDate> = todo.days.keys().copied().sorted().collect();
+        assert_eq!(
+            dates,
+            vec![
+                NaiveDate::from_ymd_opt(2024, 3, 5).unwrap(),
+                NaiveDate::from_ymd_opt(2024, 3, 7).unwrap()
+            ]
+        );
+        let lines: Vec<usize> = errors
+            .iter()
+            .filter_map(|e| e.location())
+            .map(|location| location.line)
+            .collect();
+        assert_eq!(lines, vec![3, 6]);
+        assert!(errors
+            .iter()
+            .all(|e| e.location().unwrap().path == Some(path.clone())));
+    }
+
     #[test]
     fn save_todo() {
         let expected = indoc! {"
@@ -357,19 +816,14 @@ mod tests {
                     NaiveDate::from_ymd_opt(2024, 3, 6).unwrap(),
                     Day {
                         date: NaiveDate::from_ymd_opt(2024, 3, 6).unwrap(),
+                        planned: false,
                         sections: vec![
                             Section {
                                 name: "Section 1".to_string(),
                                 tasks: vec![
-                                    Task {
-                                        text: "task 1".to_string(),
-                                    },
-                                    Task {
-                                        text: "task 3".to_string(),
-                                    },
-                                    Task {
-                                        text: "task 2".to_string(),
-                                    },
+                                    Task::new("task 1"),
+                                    Task::new("task 3"),
+                                    Task::new("task 2"),
                                 ],
                             },
                             Section {
@@ -383,19 +837,14 @@ mod tests {
                     NaiveDate::from_ymd_opt(2024, 3, 7).unwrap(),
                     Day {
                         date: NaiveDate::from_ymd_opt(2024, 3, 7).unwrap(),
+                        planned: false,
                         sections: vec![
                             Section {
                                 name: "Section 2".to_string(),
                                 tasks: vec![
-                                    Task {
-                                        text: "task 11".to_string(),
-                                    },
-                                    Task {
-                                        text: "task 31".to_string(),
-                                    },
-                                    Task {
-                                        text: "task 21".to_string(),
-                                    },
+                                    Task::new("task 11"),
+                                    Task::new("task 31"),
+                                    Task::new("task 21"),
                                 ],
                             },
                             Section {
@@ -406,12 +855,240 @@ mod tests {
                     },
                 ),
             ]),
+            ..Default::default()
         };
 
-        let _ = todo.save().expect("Unable to load file");
+        todo.save().expect("Unable to load file");
+
+        let actual = read_to_string(path).expect("Unable to read file");
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn save_keeps_formatting() {
+        let header = today().format("[%Y-%m-%d]");
+        let file = create_file_with_contents(format!(
+            "My todo file\n\n{header}\nWork  \n-   deploy\n\n\n- review\n\n[2024-03-06]\nWork\n-   deploy\n"
+        ));
+        let path = file.path().to_path_buf();
+
+        let mut todo = Todo::load(&path).expect("Unable to load file");
+        todo.set_clock(FixedClock(today()));
+        todo.add("- write docs", "Work").unwrap();
+        todo.save().expect("Unable to save file");
 
+        let expected = format!(
+            "My todo file\n\n{header}\nWork  \n-   deploy\n\n\n- review\n- write docs\n\n[2024-03-06]\nWork\n-   deploy\n"
+        );
+        let actual = read_to_string(path).expect("Unable to read file");
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn format_todo_file() {
+        let file = create_file_with_contents(
+            "[2024-03-06]\nWork  \n-   deploy\n[2024-03-07]\n\tWork\n".to_string(),
+        );
+        let path = file.path().to_path_buf();
+
+        let mut todo = Todo::load(&path).expect("Unable to load file");
+        todo.format().expect("Unable to format file");
+        let expected = "[2024-03-07]\nWork\n\n[2024-03-06]\nWork\n- deploy\n";
         let actual = read_to_string(&path).expect("Unable to read file");
         assert_eq!(actual, expected);
+
+        // formatting again changes nothing
+        assert!(matches!(todo.format(), Err(Error::UpToDate { .. })));
+    }
+
+    #[test]
+    fn query_tasks() {
+        let todo: Todo = indoc! {"
+            [2024-03-06]
+            Work
+            - deploy
+            - [x] review
+
+            [2024-03-07]
+            Work
+            - deploy again
+        "}
+        .parse()
+        .unwrap();
+
+        let actual: Vec<(NaiveDate, &str, &str)> = todo
+            .query("section:Work and not done")
+            .unwrap()
+            .into_iter()
+            .map(|(date, section, task)| (date, section.name.as_str(), task.text.as_str()))
+            .collect();
+        let expected = vec![
+            (
+                NaiveDate::from_ymd_opt(2024, 3, 7).unwrap(),
+                "Work",
+                "deploy again",
+            ),
+            (
+                NaiveDate::from_ymd_opt(2024, 3, 6).unwrap(),
+                "Work",
+                "deploy",
+            ),
+        ];
+        assert_eq!(actual, expected);
+        assert!(todo.query("section:").is_err());
+
+        // rolled up parents are done for queries and counts alike
+        let todo: Todo = "[2024-03-06]\n- release\n  - [x] build\n  - [-] publish\n"
+            .parse()
+            .unwrap();
+        assert_eq!(todo.query("done").unwrap().len(), 2);
+        assert_eq!(todo.count(Status::Done), 2);
+    }
+
+    #[test]
+    fn search_tasks() {
+        let todo: Todo = indoc! {"
+            [2024-03-06]
+            Work
+            - call Bob
+              about the release
+            Home
+            - fix sink
+
+            [2024-03-07]
+            - Release v2
+        "}
+        .parse()
+        .unwrap();
+
+        let options = search::SearchOptions {
+            ignore_case: true,
+            ..Default::default()
+        };
+        let pattern = Pattern::new("release", options).unwrap();
+        let actual: Vec<(NaiveDate, &str, &str)> = todo
+            .search(&pattern)
+            .map(|(date, section, task)| (date, section.name.as_str(), task.text.as_str()))
+            .collect();
+        let expected = vec![
+            (
+                NaiveDate::from_ymd_opt(2024, 3, 7).unwrap(),
+                "",
+                "Release v2",
+            ),
+            (
+                NaiveDate::from_ymd_opt(2024, 3, 6).unwrap(),
+                "Work",
+                "call Bob",
+            ),
+        ];
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn address_tasks() {
+        let mut todo: Todo = indoc! {"
+            [2024-03-06]
+            - call Bob
+            Work
+            - deploy
+              - build
+            - review
+
+            [2024-03-07]
+            Work
+            - deploy
+        "}
+        .parse()
+        .unwrap();
+
+        let addresses: Vec<String> = todo
+            .addresses()
+            .iter()
+            .map(|(address, _, _)| address.to_string())
+            .collect();
+        let expected = [
+            "2024-03-07/Work/1",
+            "2024-03-06//1",
+            "2024-03-06/Work/1",
+            "2024-03-06/Work/1.1",
+            "2024-03-06/Work/2",
+        ];
+        assert_eq!(addresses, expected);
+
+        let address: Address = "2024-03-06/Work/1.1".parse().unwrap();
+        todo.task_mut(&address).unwrap().complete();
+        assert_eq!(todo.task(&address).unwrap().to_string(), "  - [x] build");
+
+        // hashes resolve to the same task
+        let date = NaiveDate::from_ymd_opt(2024, 3, 6).unwrap();
+        let (_, section, _) = &todo.addresses()[4];
+        let hash = Address::Hash(content_hash(date, section, &[2])[..4].to_string());
+        assert_eq!(
+            todo.resolve(&hash).unwrap().to_string(),
+            "2024-03-06/Work/2"
+        );
+
+        let missing: Address = "2024-03-06/Work/3".parse().unwrap();
+        assert!(todo.task_mut(&missing).is_err());
+    }
+
+    #[test]
+    fn resolve_duplicate_texts() {
+        let todo: Todo = indoc! {"
+            [2024-03-06]
+            Work
+            - deploy
+              - build
+            - deploy
+              - build
+        "}
+        .parse()
+        .unwrap();
+        let date = NaiveDate::from_ymd_opt(2024, 3, 6).unwrap();
+        let section = &todo.days[&date].sections[0];
+        for position in ["1", "1.1", "2", "2.1"] {
+            let address: Address = format!("2024-03-06/Work/{position}").parse().unwrap();
+            let Address::Position { position, .. } = &address else {
+                unreachable!()
+            };
+            let hash = Address::Hash(content_hash(date, section, position));
+            assert_eq!(todo.resolve(&hash).unwrap(), address);
+        }
+    }
+
+    #[test]
+    fn edit_tasks() {
+        let mut todo: Todo = indoc! {"
+            [2024-03-06]
+            Work
+            - [x] deploy
+              - build
+            - review
+            - write docs
+        "}
+        .parse()
+        .unwrap();
+        let address = |s: &str| -> Address { s.parse().unwrap() };
+
+        todo.edit(&address("2024-03-06/Work/1"), "- deploy v2")
+            .unwrap();
+        todo.reorder(&address("2024-03-06/Work/3"), 1).unwrap();
+        todo.move_task(&address("2024-03-06/Work/2.1"), "Home")
+            .unwrap();
+        todo.delete(&address("2024-03-06/Work/3")).unwrap();
+
+        let expected = indoc! {"
+            [2024-03-06]
+            Work
+            - write docs
+            - [x] deploy v2
+
+            Home
+            - build"};
+        assert_eq!(todo.to_string(), expected);
+        assert!(todo.delete(&address("2024-03-06/Work/3")).is_err());
+        assert!(todo.reorder(&address("2024-03-07/Work/1"), 1).is_err());
     }
 
     #[test]
@@ -421,19 +1098,14 @@ mod tests {
                 today(),
                 Day {
                     date: today(),
+                    planned: false,
                     sections: vec![
                         Section {
                             name: "Section 1".to_string(),
                             tasks: vec![
-                                Task {
-                                    text: "task 1".to_string(),
-                                },
-                                Task {
-                                    text: "task 2".to_string(),
-                                },
-                                Task {
-                                    text: "task 3".to_string(),
-                                },
+                                Task::new("task 1"),
+                                Task::new("task 2"),
+                                Task::new("task 3"),
                             ],
                         },
                         Section {
@@ -444,6 +1116,7 @@ mod tests {
                 },
             )]),
             file_path: PathBuf::new(),
+            ..Default::default()
         };
 
         let expected = Todo {
@@ -451,22 +1124,15 @@ mod tests {
                 today(),
                 Day {
                     date: today(),
+                    planned: false,
                     sections: vec![
                         Section {
                             name: "Section 1".to_string(),
                             tasks: vec![
-                                Task {
-                                    text: "task 1".to_string(),
-                                },
-                                Task {
-                                    text: "task 2".to_string(),
-                                },
-                                Task {
-                                    text: "task 3".to_string(),
-                                },
-                                Task {
-                                    text: "added task".to_string(),
-                                },
+                                Task::new("task 1"),
+                                Task::new("task 2"),
+                                Task::new("task 3"),
+                                Task::new("added task"),
                             ],
                         },
                         Section {
@@ -477,9 +1143,11 @@ mod tests {
                 },
             )]),
             file_path: PathBuf::new(),
+            ..Default::default()
         };
 
         let mut actual = base.clone();
+        actual.set_clock(FixedClock(today()));
         actual.add("- added task", "Section 1").unwrap();
         assert_eq!(actual, expected);
     }
@@ -491,19 +1159,14 @@ mod tests {
                 today(),
                 Day {
                     date: today(),
+                    planned: false,
                     sections: vec![
                         Section {
                             name: "Section 1".to_string(),
                             tasks: vec![
-                                Task {
-                                    text: "task 1".to_string(),
-                                },
-                                Task {
-                                    text: "task 2".to_string(),
-                                },
-                                Task {
-                                    text: "task 3".to_string(),
-                                },
+                                Task::new("task 1"),
+                                Task::new("task 2"),
+                                Task::new("task 3"),
                             ],
                         },
                         Section {
@@ -514,6 +1177,7 @@ mod tests {
                 },
             )]),
             file_path: PathBuf::new(),
+            ..Default::default()
         };
 
         let expected = Todo {
@@ -521,19 +1185,14 @@ mod tests {
                 today(),
                 Day {
                     date: today(),
+                    planned: false,
                     sections: vec![
                         Section {
                             name: "Section 1".to_string(),
                             tasks: vec![
-                                Task {
-                                    text: "task 1".to_string(),
-                                },
-                                Task {
-                                    text: "task 2".to_string(),
-                                },
-                                Task {
-                                    text: "task 3".to_string(),
-                                },
+                                Task::new("task 1"),
+                                Task::new("task 2"),
+                                Task::new("task 3"),
                             ],
                         },
                         Section {
@@ -543,21 +1202,255 @@ mod tests {
                         // new section is added to end of vec, not before Done
                         Section {
                             name: "New Section".to_string(),
-                            tasks: vec![Task {
-                                text: "added task".to_string(),
-                            }],
+                            tasks: vec![Task::new("added task")],
                         },
                     ],
                 },
             )]),
             file_path: PathBuf::new(),
+            ..Default::default()
         };
 
         let mut actual = base.clone();
+        actual.set_clock(FixedClock(today()));
         actual.add("- added task", "New Section").unwrap();
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn complete_task() {
+        let mut actual = Todo {
+            days: HashMap::from([(
+                today(),
+                Day {
+                    date: today(),
+                    planned: false,
+                    sections: vec![Section {
+                        name: "Section 1".to_string(),
+                        tasks: vec![Task::new("task 1"), Task::new("task 2")],
+                    }],
+                },
+            )]),
+            file_path: PathBuf::new(),
+            ..Default::default()
+        };
+
+        let mut done = Task::new("task 2");
+        done.complete();
+        let expected = Todo {
+            days: HashMap::from([(
+                today(),
+                Day {
+                    date: today(),
+                    planned: false,
+                    sections: vec![Section {
+                        name: "Section 1".to_string(),
+                        tasks: vec![Task::new("task 1"), done],
+                    }],
+                },
+            )]),
+            file_path: PathBuf::new(),
+            ..Default::default()
+        };
+
+        actual.set_clock(FixedClock(today()));
+        actual.complete("- task 2", "Section 1").unwrap();
+        assert_eq!(actual, expected);
+
+        // completed tasks can't be completed again
+        assert!(actual.complete("task 2", "Section 1").is_err());
+        assert!(actual.complete("task 3", "Section 1").is_err());
+    }
+
+    #[test]
+    fn complete_subtask_rolls_up() {
+        let mut todo: Todo = indoc! {"
+            [2024-03-06]
+            Section 1
+            - task 1
+              - step 1
+              - [x] step 2
+        "}
+        .parse()
+        .unwrap();
+        let mut day = todo.days.values().next().unwrap().clone();
+        day.date = today();
+        todo.days = HashMap::from([(today(), day)]);
+        todo.set_clock(FixedClock(today()));
+
+        todo.complete("step 1", "Section 1").unwrap();
+        let section = &todo.days[&today()].sections[0];
+        assert!(section.tasks[0].subtasks[0].is_done());
+        assert_eq!(section.tasks[0].status, Status::Done);
+    }
+
+    #[test]
+    fn add_task_rolls_over() {
+        let mut todo: Todo = "[2024-03-06]\nWork\n- [x] deploy\n- review\nDone\n- [x] lunch\n"
+            .parse()
+            .unwrap();
+        let today = NaiveDate::from_ymd_opt(2024, 3, 7).unwrap();
+        todo.set_clock(FixedClock(today));
+        todo.config.rollover = Rollover {
+            carry: rollover::Carry::NotDone,
+            clear: vec!["Done".to_string()],
+            ..Default::default()
+        };
+        todo.add("- write docs", "Work").unwrap();
+
+        let day = &todo.days[&today];
+        assert_eq!(
+            day.sections[0].tasks,
+            vec![Task::new("review"), Task::new("write docs")]
+        );
+        assert!(day.sections[1].tasks.is_empty());
+    }
+
+    #[test]
+    fn add_task_to_past_day() {
+        let date = |d| NaiveDate::from_ymd_opt(2024, 3, d).unwrap();
+        let mut todo: Todo = "[2024-03-07]\nWork\n- deploy\n\n[2024-03-01]\nWork\n- review\n"
+            .parse()
+            .unwrap();
+        todo.set_clock(FixedClock(date(7)));
+        todo.backfill(date(4), "- write docs", "Work").unwrap();
+        todo.backfill(date(1), "- lunch", "").unwrap();
+        assert!(todo.backfill(date(7), "- too late", "Work").is_err());
+
+        // nothing is carried into 03-04, days before it are only appended to
+        let expected = indoc! {"
+            [2024-03-07]
+            Work
+            - deploy
+
+            [2024-03-04]
+            Work
+            - write docs
+
+            [2024-03-01]
+            Work
+            - review
+
+            - lunch
+        "};
+        let tree = todo.syntax_tree().unwrap();
+        assert_eq!(tree.render(&todo.days), expected);
+    }
+
+    #[test]
+    fn merge_todos() {
+        let mut todo: Todo = "[2024-03-07]\nWork\n- deploy\n\n[2024-03-06]\nWork\n- review\n"
+            .parse()
+            .unwrap();
+        let imported: Todo = "[2024-03-08]\n- lunch\n\n[2024-03-07]\nWork\n- [x] deploy\n- write docs\nHome\n- groceries\n"
+            .parse()
+            .unwrap();
+        todo.merge(imported);
+
+        let expected = indoc! {"
+            [2024-03-08]
+
+            - lunch
+
+            [2024-03-07]
+            Work
+            - deploy
+            - write docs
+
+            Home
+            - groceries
+
+            [2024-03-06]
+            Work
+            - review
+        "};
+        let tree = todo.syntax_tree().unwrap();
+        assert_eq!(tree.render(&todo.days), expected);
+    }
+
+    #[test]
+    fn roll_over_into_unmarked_day() {
+        let date = |d| NaiveDate::from_ymd_opt(2024, 3, d).unwrap();
+        let file = create_file_with_contents(
+            "[2024-03-09]\nWork\n- deploy\n\n[2024-03-07]\nWork\n- review\n".to_string(),
+        );
+        let mut todo = Todo::load(&file.path().to_path_buf()).unwrap();
+        todo.set_clock(FixedClock(date(7)));
+        assert!(todo.days[&date(9)].planned);
+
+        // planning for a day written without the marker keeps it planned
+        todo.plan(date(9), "- write docs", "Work").unwrap();
+        assert_eq!(todo.upcoming().len(), 1);
+
+        todo.set_clock(FixedClock(date(9)));
+        todo.add("- lunch", "").unwrap();
+        let expected = indoc! {"
+            [2024-03-09]
+            Work
+            - review
+            - deploy
+            - write docs
+
+            - lunch
+
+            [2024-03-07]
+            Work
+            - review
+        "};
+        let tree = todo.syntax_tree().unwrap();
+        assert_eq!(tree.render(&todo.days), expected);
+    }
+
+    #[test]
+    fn plan_and_roll_over() {
+        let date = |d| NaiveDate::from_ymd_opt(2024, 3, d).unwrap();
+        let mut todo: Todo = "[2024-03-07]\nWork\n- review\n".parse().unwrap();
+        todo.set_clock(FixedClock(date(7)));
+        todo.plan(date(9), "- deploy", "Work").unwrap();
+        todo.plan(date(8), "- call mom", "").unwrap();
+        todo.plan(date(9), "- write docs", "Work").unwrap();
+        assert!(todo.plan(date(7), "- too late", "Work").is_err());
+
+        let expected = indoc! {"
+            [2024-03-09] (planned)
+            Work
+            - deploy
+            - write docs
+
+            [2024-03-08] (planned)
+
+            - call mom
+
+            [2024-03-07]
+            Work
+            - review
+        "};
+        let tree = todo.syntax_tree().unwrap();
+        assert_eq!(tree.render(&todo.days), expected);
+
+        // 03-08 went by without a rollover, both planned days are merged into 03-09
+        todo.set_clock(FixedClock(date(9)));
+        todo.add("- lunch", "").unwrap();
+        let expected = indoc! {"
+            [2024-03-09]
+            Work
+            - review
+            - deploy
+            - write docs
+
+
+            - call mom
+            - lunch
+
+            [2024-03-07]
+            Work
+            - review
+        "};
+        let tree = todo.syntax_tree().unwrap();
+        assert_eq!(tree.render(&todo.days), expected);
+        assert!(todo.upcoming().is_empty());
+    }
+
     #[test]
     fn next_day() {
         let base = Todo {
@@ -565,28 +1458,21 @@ mod tests {
                 today() - ChronoDuration::days(1),
                 Day {
                     date: today() - ChronoDuration::days(1),
+                    planned: false,
                     sections: vec![
                         Section {
                             name: "Section 1".to_string(),
-                            tasks: vec![
-                                Task {
-                                    text: "task 1".to_string(),
-                                },
-                                Task {
-                                    text: "task 2".to_string(),
-                                },
-                            ],
+                            tasks: vec![Task::new("task 1"), Task::new("task 2")],
                         },
                         Section {
                             name: "Done".to_string(),
-                            tasks: vec![Task {
-                                text: "task 3".to_string(),
-                            }],
+                            tasks: vec![Task::new("task 3")],
                         },
                     ],
                 },
             )]),
             file_path: PathBuf::new(),
+            ..Default::default()
         };
 
         let expected = Todo {
@@ -595,17 +1481,11 @@ mod tests {
                     today(),
                     Day {
                         date: today(),
+                        planned: false,
                         sections: vec![
                             Section {
                                 name: "Section 1".to_string(),
-                                tasks: vec![
-                                    Task {
-                                        text: "task 1".to_string(),
-                                    },
-                                    Task {
-                                        text: "task 2".to_string(),
-                                    },
-                                ],
+                                tasks: vec![Task::new("task 1"), Task::new("task 2")],
                             },
                             Section {
                                 name: "Done".to_string(),
@@ -618,32 +1498,26 @@ mod tests {
                     today() - ChronoDuration::days(1),
                     Day {
                         date: today() - ChronoDuration::days(1),
+                        planned: false,
                         sections: vec![
                             Section {
                                 name: "Section 1".to_string(),
-                                tasks: vec![
-                                    Task {
-                                        text: "task 1".to_string(),
-                                    },
-                                    Task {
-                                        text: "task 2".to_string(),
-                                    },
-                                ],
+                                tasks: vec![Task::new("task 1"), Task::new("task 2")],
                             },
                             Section {
                                 name: "Done".to_string(),
-                                tasks: vec![Task {
-                                    text: "task 3".to_string(),
-                                }],
+                                tasks: vec![Task::new("task 3")],
                             },
                         ],
                     },
                 ),
             ]),
             file_path: PathBuf::new(),
+            ..Default::default()
         };
 
         let mut actual = base.clone();
+        actual.set_clock(FixedClock(today()));
 
         actual.next_day();
         assert_eq!(actual, expected);
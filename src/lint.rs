@@ -0,0 +1,373 @@
+//! Style and consistency checks for todo files
+
+use crate::{
+    cst::SyntaxTree,
+    err,
+    error::{render_diagnostic, Error, Location},
+    util::Result,
+    Todo,
+};
+use chrono::NaiveDate;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt, str,
+};
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Hash)]
+pub enum Rule {
+    /// The same date header appears more than once, only the last one is kept when loading
+    DuplicateDate,
+    /// The same section name appears more than once in a day
+    DuplicateSection,
+    /// Days are not sorted by date
+    DayOrder,
+    /// Section without tasks
+    EmptySection,
+    /// Line ending in spaces or tabs
+    TrailingWhitespace,
+    /// Task that doesn't start with exactly `- `
+    DashSpacing,
+}
+
+#[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy, Hash)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl Rule {
+    pub const ALL: [Rule; 6] = [
+        Rule::DuplicateDate,
+        Rule::DuplicateSection,
+        Rule::DayOrder,
+        Rule::EmptySection,
+        Rule::TrailingWhitespace,
+        Rule::DashSpacing,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Rule::DuplicateDate => "duplicate-date",
+            Rule::DuplicateSection => "duplicate-section",
+            Rule::DayOrder => "day-order",
+            Rule::EmptySection => "empty-section",
+            Rule::TrailingWhitespace => "trailing-whitespace",
+            Rule::DashSpacing => "dash-spacing",
+        }
+    }
+
+    /// Severity of the rule unless configured otherwise
+    pub fn default_severity(&self) -> Severity {
+        match self {
+            Rule::DuplicateDate => Severity::Error,
+            _ => Severity::Warning,
+        }
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl str::FromStr for Rule {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self> {
+        match Rule::ALL.into_iter().find(|rule| rule.name() == s) {
+            Some(rule) => Ok(rule),
+            None => err!("Unknown lint rule: {s}"),
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// Problem found by a lint rule
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Lint {
+    pub rule: Rule,
+    pub severity: Severity,
+    pub message: String,
+    pub location: Location,
+}
+
+impl Lint {
+    /// Renders the lint the way rustc does, pointing at the offending line
+    pub fn render(&self) -> String {
+        let level = format!("{}[{}]", self.severity, self.rule);
+        render_diagnostic(&level, &self.message, &self.location)
+    }
+}
+
+/// Runs lint rules, each rule can be turned off or have its severity changed
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Linter {
+    rules: HashMap<Rule, Option<Severity>>,
+}
+
+impl Default for Linter {
+    fn default() -> Self {
+        let rules = Rule::ALL
+            .into_iter()
+            .map(|rule| (rule, Some(rule.default_severity())))
+            .collect();
+        Linter { rules }
+    }
+}
+
+impl Linter {
+    pub fn new() -> Linter {
+        Linter::default()
+    }
+
+    /// Turns rule off
+    pub fn allow(&mut self, rule: Rule) -> &mut Linter {
+        self.rules.insert(rule, None);
+        self
+    }
+
+    /// Reports rule as a warning
+    pub fn warn(&mut self, rule: Rule) -> &mut Linter {
+        self.rules.insert(rule, Some(Severity::Warning));
+        self
+    }
+
+    /// Reports rule as an error
+    pub fn deny(&mut self, rule: Rule) -> &mut Linter {
+        self.rules.insert(rule, Some(Severity::Error));
+        self
+    }
+
+    /// Checks the file todo was read from, todos that were not read from text have nothing to check
+    pub fn check(&self, todo: &Todo) -> Vec<Lint> {
+        let Some(tree) = todo.syntax_tree() else {
+            return Vec::new();
+        };
+
+        let mut lints = Vec::new();
+        let mut report = |rule: Rule, message: String, location: Location| {
+            if let Some(severity) = self.rules[&rule] {
+                let mut location = location;
+                if !todo.file_path.as_os_str().is_empty() {
+                    location.path = Some(todo.file_path.clone());
+                }
+                lints.push(Lint {
+                    rule,
+                    severity,
+                    message,
+                    location,
+                });
+            }
+        };
+
+        check_days(tree, &mut report);
+        check_sections(tree, &mut report);
+        check_lines(tree, &mut report);
+
+        lints.sort_by_key(|lint| lint.location.line);
+        lints
+    }
+}
+
+fn check_days(tree: &SyntaxTree, report: &mut impl FnMut(Rule, String, Location)) {
+    let mut seen: HashSet<NaiveDate> = HashSet::new();
+    for node in &tree.days {
+        let header = node.head.start;
+        if !seen.insert(node.day.date) {
+            report(
+                Rule::DuplicateDate,
+                format!("Date {} appears more than once", node.day.date),
+                Location::of_line(header, tree.line(header)),
+            );
+        }
+    }
+
+    // days should follow the order of the file, newest first unless the file is oldest first
+    let ascending = match (tree.days.first(), tree.days.last()) {
+        (Some(first), Some(last)) => first.day.date < last.day.date,
+        _ => false,
+    };
+    for pair in tree.days.windows(2) {
+        let (previous, node) = (&pair[0], &pair[1]);
+        let in_order = match ascending {
+            true => previous.day.date < node.day.date,
+            false => previous.day.date > node.day.date,
+        };
+        // duplicates are already reported
+        if !in_order && previous.day.date != node.day.date {
+            let header = node.head.start;
+            let expected = if ascending { "after" } else { "before" };
+            report(
+                Rule::DayOrder,
+                format!(
+                    "Day {} should come {expected} {}",
+                    node.day.date, previous.day.date
+                ),
+                Location::of_line(header, tree.line(header)),
+            );
+        }
+    }
+}
+
+fn check_sections(tree: &SyntaxTree, report: &mut impl FnMut(Rule, String, Location)) {
+    for node in &tree.days {
+        let mut seen: HashSet<&str> = HashSet::new();
+        for section in &node.sections {
+            // anonymous sections start with a task
+            let line = section.head.start;
+            let location = Location::of_line(line, tree.line(line));
+            let name = section.section.name.as_str();
+            if !seen.insert(name) {
+                report(
+                    Rule::DuplicateSection,
+                    format!("Section {name} appears more than once in {}", node.day.date),
+                    location.clone(),
+                );
+            }
+            if section.section.tasks.is_empty() {
+                report(
+                    Rule::EmptySection,
+                    format!("Section {name} has no tasks"),
+                    location,
+                );
+            }
+        }
+    }
+}
+
+fn check_lines(tree: &SyntaxTree, report: &mut impl FnMut(Rule, String, Location)) {
+    for index in 0..tree.line_count() {
+        let line = tree.line(index);
+        let trimmed = line.trim_end();
+        if trimmed.len() != line.len() {
+            report(
+                Rule::TrailingWhitespace,
+                "Trailing whitespace".to_string(),
+                Location {
+                    path: None,
+                    line: index + 1,
+                    column: trimmed.chars().count() + 1,
+                    len: line[trimmed.len()..].chars().count(),
+                    source_line: line.to_string(),
+                },
+            );
+        }
+    }
+
+    let task_lines = tree
+        .days
+        .iter()
+        .flat_map(|day| &day.sections)
+        .flat_map(|section| &section.tasks)
+        .flat_map(|task| task.lines.clone());
+    for index in task_lines {
+        let line = tree.line(index);
+        let Some(rest) = line.trim_start().strip_prefix('-') else {
+            continue; // notes and blank lines
+        };
+        let text = rest.trim_start();
+        if (!text.is_empty() && !rest.starts_with(' ')) || rest.len() - text.len() > 1 {
+            report(
+                Rule::DashSpacing,
+                "Tasks should start with a dash followed by a single space".to_string(),
+                Location::of_line(index, line),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use indoc::indoc;
+
+    fn rules(lints: &[Lint]) -> Vec<(Rule, usize)> {
+        lints
+            .iter()
+            .map(|lint| (lint.rule, lint.location.line))
+            .collect()
+    }
+
+    #[test]
+    fn check_todo() {
+        let todo: Todo = indoc! {"
+            [2024-03-07]
+            Work
+            - task 1
+            -task 2
+            Work
+            -   task 3
+            Done
+
+            [2024-03-05]
+            - task 4 \t
+
+            [2024-03-06]
+            - task 5
+
+            [2024-03-05]
+            - task 6
+        "}
+        .parse()
+        .unwrap();
+
+        let lints = Linter::new().check(&todo);
+        let expected = vec![
+            (Rule::DashSpacing, 4),
+            (Rule::DuplicateSection, 5),
+            (Rule::DashSpacing, 6),
+            (Rule::EmptySection, 7),
+            (Rule::TrailingWhitespace, 10),
+            (Rule::DayOrder, 12),
+            (Rule::DuplicateDate, 15),
+        ];
+        assert_eq!(rules(&lints), expected);
+        assert_eq!(lints[6].severity, Severity::Error);
+    }
+
+    #[test]
+    fn configure_rules() {
+        let todo: Todo = indoc! {"
+            [2024-03-07]
+            Done
+            -task
+        "}
+        .parse()
+        .unwrap();
+
+        let lints = Linter::new()
+            .allow(Rule::DashSpacing)
+            .deny(Rule::EmptySection)
+            .check(&todo);
+        assert!(lints.is_empty());
+
+        let todo: Todo = "[2024-03-07]\nDone\n".parse().unwrap();
+        let lints = Linter::new().deny(Rule::EmptySection).check(&todo);
+        assert_eq!(rules(&lints), vec![(Rule::EmptySection, 2)]);
+        assert_eq!(lints[0].severity, Severity::Error);
+    }
+
+    #[test]
+    fn render_lint() {
+        let todo: Todo = "[2024-03-07]\n- task \n".parse().unwrap();
+        let lints = Linter::new().check(&todo);
+        let expected = [
+            "warning[trailing-whitespace]: Trailing whitespace",
+            " --> line 2, column 7",
+            "  |",
+            "2 | - task ",
+            "  |       ^",
+        ]
+        .join("\n");
+        assert_eq!(lints[0].render(), expected);
+    }
+}
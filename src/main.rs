@@ -1,16 +1,62 @@
-use clap::{Parser, Subcommand};
-use std::path::PathBuf;
-use std::{error, result};
+use clap::{Parser, Subcommand, ValueEnum};
+use std::env;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
 
-use netxt::Todo;
-
-type Result<T> = result::Result<T, Box<dyn error::Error + Send + Sync>>;
+use chrono::NaiveDate;
+use netxt::address::{content_hash, Address};
+use netxt::config::{Config, Overrides};
+use netxt::filter::Filter;
+use netxt::lint::{Linter, Rule, Severity};
+use netxt::query::Query;
+use netxt::rollover::Carry;
+use netxt::search::{Pattern, SearchOptions};
+use netxt::{err, format, html, ics, markdown, todotxt, Error, Result, Section, Task, Todo};
 
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+    /// Todo file to use, defaults to NETXT_FILE, then the closest todo.txt or .netxt up from the
+    /// current directory, then the file in the config
+    #[arg(short, long, global = true, value_name = "FILE")]
+    file: Option<PathBuf>,
+    /// Read settings from this file instead of ~/.config/netxt/config.toml
+    #[arg(long, global = true, value_name = "FILE")]
+    config: Option<PathBuf>,
+    /// Format of day headers e.g. `[%Y-%m-%d]`
+    #[arg(long, global = true, value_name = "FORMAT")]
+    date_format: Option<String>,
+    /// Section that starts empty every day
+    #[arg(long, global = true, value_name = "NAME")]
+    done_section: Option<String>,
+    /// Hour new days start at, from 0 to 23
+    #[arg(long, global = true, value_name = "HOUR")]
+    day_start_hour: Option<u32>,
+    /// IANA name of the timezone days follow e.g. Europe/Lisbon
+    #[arg(long, global = true, value_name = "NAME")]
+    timezone: Option<String>,
+    /// Tasks copied to a new day: all, not-done or open
+    #[arg(long, global = true, value_name = "POLICY")]
+    rollover_carry: Option<Carry>,
+    /// Sections that start empty on a new day, comma separated
+    #[arg(long, global = true, value_name = "NAMES", value_delimiter = ',')]
+    rollover_clear: Option<Vec<String>>,
+    /// Sections copied to a new day with all of their tasks, comma separated
+    #[arg(long, global = true, value_name = "NAMES", value_delimiter = ',')]
+    rollover_pinned: Option<Vec<String>>,
+    /// Sections every new day starts with, comma separated
+    #[arg(long, global = true, value_name = "NAMES", value_delimiter = ',')]
+    rollover_template: Option<Vec<String>>,
+    /// Columns a tab expands to when formatting
+    #[arg(long, global = true, value_name = "COLUMNS")]
+    tab_width: Option<usize>,
+    /// Blank lines between sections and between days when formatting
+    #[arg(long, global = true, value_name = "LINES")]
+    blank_lines: Option<usize>,
 }
 
 #[derive(Subcommand)]
@@ -18,35 +64,444 @@ enum Commands {
     /// Create new todo file
     Init,
     /// Parse existing todo file
-    Parse { todo_file: PathBuf },
+    Parse,
+    /// Add task to today's section
     Add {
-        todo_file: PathBuf,
         task: String,
         section: Option<String>,
+        /// Add to this day instead of today e.g. 2024-03-01, days after today are planned and past days get nothing rolled over
+        #[arg(long)]
+        date: Option<NaiveDate>,
+    },
+    /// Report style and consistency problems, fails if any error is found
+    ///
+    /// Rules: duplicate-date, duplicate-section, day-order, empty-section, trailing-whitespace, dash-spacing
+    Check {
+        /// Turn rule off
+        #[arg(long, value_name = "RULE")]
+        allow: Vec<Rule>,
+        /// Report rule as a warning
+        #[arg(long, value_name = "RULE")]
+        warn: Vec<Rule>,
+        /// Report rule as an error
+        #[arg(long, value_name = "RULE")]
+        deny: Vec<Rule>,
+    },
+    /// Rewrite todo file in canonical form
+    Fmt {
+        /// Don't write the file, print a diff and fail if it is not formatted
+        #[arg(long)]
+        check: bool,
+    },
+    /// List tasks matching a query e.g. `section:Work and not done`, or all tasks
+    List {
+        query: Vec<String>,
+        /// Only list days after today, soonest first
+        #[arg(long)]
+        upcoming: bool,
+    },
+    /// List tasks with their addresses, optionally only those matching a query
+    Ls { query: Vec<String> },
+    /// Search task text and notes, newest days first
+    Search {
+        pattern: String,
+        /// Treat pattern as a regular expression
+        #[arg(short = 'E', long)]
+        regex: bool,
+        #[arg(short, long)]
+        ignore_case: bool,
+        /// Only match whole words
+        #[arg(short, long)]
+        word: bool,
+        /// Stop after this many matching tasks
+        #[arg(short, long, value_name = "NUM")]
+        max_count: Option<usize>,
+    },
+    /// Replace the text of a task, addresses are printed by `ls`
+    Edit { address: Address, text: String },
+    /// Delete a task and its subtasks
+    Delete { address: Address },
+    /// Move a task to the end of another section of its day
+    Move { address: Address, section: String },
+    /// Move a task to another place in its section, places start at 1
+    Reorder { address: Address, place: usize },
+    /// Mark task as done in today's section
+    Done {
+        task: String,
+        section: Option<String>,
+    },
+    /// Write the todo file in another format, to stdout unless an output file is given
+    Export {
+        #[arg(long, value_enum)]
+        format: FileFormat,
+        /// File to write to, or directory for html
+        #[arg(short, long, visible_alias = "out", value_name = "PATH")]
+        output: Option<PathBuf>,
+        /// Only export days on or after this date
+        #[arg(long, value_name = "DATE")]
+        from: Option<NaiveDate>,
+        /// Only export days on or before this date
+        #[arg(long, value_name = "DATE")]
+        to: Option<NaiveDate>,
+        /// Only export sections with this name, can be given more than once
+        #[arg(long = "section", value_name = "NAME")]
+        sections: Vec<String>,
+    },
+    /// Add the days of a document to the todo file, read from stdin unless a file is given
+    ///
+    /// Tasks are added to the days and sections they are in, tasks already there are left as they are
+    Import {
+        #[arg(long, value_enum)]
+        format: FileFormat,
+        input: Option<PathBuf>,
     },
 }
 
-fn main() -> Result<()> {
+/// Formats todos can be exported to and imported from
+#[derive(Clone, Copy, ValueEnum)]
+enum FileFormat {
+    /// JSON document of days, sections and tasks, see the docs of netxt::json for the schema
+    Json,
+    /// Headings for days and sections with checklists of tasks, export only
+    Markdown,
+    /// Static site with a calendar, a page per day and per section and search, export only
+    Html,
+    /// iCalendar with tasks that have due, scheduled or at properties, export only
+    Ics,
+    /// todo.txt with a line per task, sections are +projects
+    #[value(name = "todo.txt", alias = "todotxt")]
+    TodoTxt,
+}
+
+fn main() -> ExitCode {
     let cli = Cli::parse();
+    match run(&cli) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{}", e.render());
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Settings from the config file and environment, overridden by command line flags
+fn config(cli: &Cli) -> Result<Config> {
+    let config = Config::load(cli.config.as_deref())?;
+    config.with_overrides(Overrides {
+        done_section: cli.done_section.clone(),
+        date_format: cli.date_format.clone(),
+        day_start_hour: cli.day_start_hour,
+        timezone: cli.timezone.clone(),
+        carry: cli.rollover_carry,
+        clear: cli.rollover_clear.clone(),
+        pinned: cli.rollover_pinned.clone(),
+        template: cli.rollover_template.clone(),
+        tab_width: cli.tab_width,
+        blank_lines: cli.blank_lines,
+    })
+}
+
+fn run(cli: &Cli) -> Result<()> {
+    let mut config = config(cli)?;
+    let file = cli
+        .file
+        .clone()
+        .or_else(|| env::var_os("NETXT_FILE").map(PathBuf::from));
+    let todo_file = &match &file {
+        Some(file) => file.clone(),
+        None => config.find_file(&env::current_dir().unwrap_or_default()),
+    };
     match &cli.command {
         Commands::Init => {
-            let mut todo = Todo::new(None)?;
+            // a new file is created where asked or at the configured path, never where one was found
+            if let Some(file) = file {
+                config.file = file;
+            }
+            let mut todo = Todo::new_with(None, &config)?;
             todo.save()?;
             Ok(())
         }
-        Commands::Parse { todo_file } => {
-            let _todo = Todo::load(todo_file)?;
-            Ok(())
+        Commands::Parse => {
+            // report every problem in the file at once
+            let (_todo, errors) = Todo::load_lenient_with(todo_file, &config)?;
+            for e in &errors {
+                eprintln!("{}\n", e.render());
+            }
+            match errors.len() {
+                0 => Ok(()),
+                n => err!("Found {n} problem(s) in {}", todo_file.display()),
+            }
         }
         Commands::Add {
-            todo_file,
             task,
             section,
+            date,
         } => {
             let section = section.clone().unwrap_or("".to_string());
-            let mut todo = Todo::load(todo_file)?;
-            todo.add(&task, &section)?;
+            let mut todo = Todo::load_with(todo_file, &config)?;
+            match date {
+                Some(date) if *date > todo.today() => todo.plan(*date, task, &section)?,
+                Some(date) if *date < todo.today() => todo.backfill(*date, task, &section)?,
+                _ => todo.add(task, &section)?,
+            }
+            todo.save()
+        }
+        Commands::Check { allow, warn, deny } => {
+            let mut linter = Linter::new();
+            for rule in allow {
+                linter.allow(*rule);
+            }
+            for rule in warn {
+                linter.warn(*rule);
+            }
+            for rule in deny {
+                linter.deny(*rule);
+            }
+
+            let (todo, errors) = Todo::load_lenient_with(todo_file, &config)?;
+            let lints = linter.check(&todo);
+            for e in &errors {
+                eprintln!("{}\n", e.render());
+            }
+            for lint in &lints {
+                eprintln!("{}\n", lint.render());
+            }
+
+            let error_count = errors.len()
+                + lints
+                    .iter()
+                    .filter(|lint| lint.severity == Severity::Error)
+                    .count();
+            let warning_count = errors.len() + lints.len() - error_count;
+            match error_count {
+                0 => Ok(()),
+                n => err!(
+                    "Found {n} error(s) and {warning_count} warning(s) in {}",
+                    todo_file.display()
+                ),
+            }
+        }
+        Commands::Fmt { check } => {
+            let mut todo = Todo::load_with(todo_file, &config)?;
+            if !check {
+                return match todo.format() {
+                    Ok(()) | Err(Error::UpToDate { .. }) => Ok(()),
+                    Err(e) => Err(e),
+                };
+            }
+            let original = todo.syntax_tree().map(|tree| tree.source());
+            let original = original.unwrap_or_default();
+            let formatted = format::format(&todo)?;
+            if original == formatted {
+                return Ok(());
+            }
+            print!("{}", format::diff(todo_file, &original, &formatted));
+            err!("{} is not formatted", todo_file.display())
+        }
+        Commands::List { query, upcoming } => {
+            let todo = Todo::load_with(todo_file, &config)?;
+            let mut tasks = match query.is_empty() {
+                true => todo.iter_tasks().collect(),
+                false => todo.query(&query.join(" "))?,
+            };
+            if *upcoming {
+                let today = todo.today();
+                tasks.retain(|(date, ..)| *date > today);
+                tasks.sort_by_key(|(date, ..)| *date);
+            }
+            print_tasks(&tasks, &config.date_format);
             Ok(())
         }
+        Commands::Ls { query } => {
+            let todo = Todo::load_with(todo_file, &config)?;
+            let query: Option<Query> = match query.is_empty() {
+                true => None,
+                false => Some(Query::parse(&query.join(" "), todo.today())?),
+            };
+            let addresses = todo.addresses();
+            let tasks: Vec<_> = addresses
+                .iter()
+                .filter_map(|(address, section, task)| match address {
+                    Address::Position { date, position, .. } => {
+                        Some((address, *date, *section, *task, position))
+                    }
+                    Address::Hash(_) => None,
+                })
+                .filter(|(_, date, section, task, _)| {
+                    query
+                        .as_ref()
+                        .is_none_or(|q| q.matches(*date, section, task))
+                })
+                .collect();
+            let width = tasks
+                .iter()
+                .map(|(address, ..)| address.to_string().len())
+                .max()
+                .unwrap_or(0);
+            for (address, date, section, task, position) in tasks {
+                let hash = content_hash(date, section, position);
+                let address = address.to_string();
+                println!("{address:width$}  {hash}  {}{}", task.indent, task.line());
+            }
+            Ok(())
+        }
+        Commands::Search {
+            pattern,
+            regex,
+            ignore_case,
+            word,
+            max_count,
+        } => {
+            let options = SearchOptions {
+                regex: *regex,
+                ignore_case: *ignore_case,
+                whole_word: *word,
+            };
+            let pattern = Pattern::new(pattern, options)?;
+            let todo = Todo::load_with(todo_file, &config)?;
+            let hits = todo.search(&pattern).take(max_count.unwrap_or(usize::MAX));
+            for (date, section, task) in hits {
+                let date = date.format(&config.date_format);
+                match section.name.is_empty() {
+                    true => println!("{date} {}", task.line()),
+                    false => println!("{date} {}: {}", section.name, task.line()),
+                }
+                for note in task
+                    .notes
+                    .iter()
+                    .filter(|note| pattern.is_match(&note.text))
+                {
+                    println!("    {}", note.text);
+                }
+            }
+            Ok(())
+        }
+        Commands::Edit { address, text } => {
+            let mut todo = Todo::load_with(todo_file, &config)?;
+            todo.edit(address, text)?;
+            todo.save()
+        }
+        Commands::Delete { address } => {
+            let mut todo = Todo::load_with(todo_file, &config)?;
+            todo.delete(address)?;
+            todo.save()
+        }
+        Commands::Move { address, section } => {
+            let mut todo = Todo::load_with(todo_file, &config)?;
+            todo.move_task(address, section)?;
+            todo.save()
+        }
+        Commands::Reorder { address, place } => {
+            let mut todo = Todo::load_with(todo_file, &config)?;
+            todo.reorder(address, *place)?;
+            todo.save()
+        }
+        Commands::Done { task, section } => {
+            let section = section.clone().unwrap_or("".to_string());
+            let mut todo = Todo::load_with(todo_file, &config)?;
+            todo.complete(task, &section)?;
+            todo.save()?;
+            Ok(())
+        }
+        Commands::Export {
+            format,
+            output,
+            from,
+            to,
+            sections,
+        } => {
+            let todo = Todo::load_with(todo_file, &config)?;
+            let filter = Filter {
+                from: *from,
+                to: *to,
+                sections: sections.clone(),
+            };
+            let todo = filter.apply(&todo);
+            if let FileFormat::Html = format {
+                let Some(dir) = output else {
+                    return err!("Exporting html writes a directory, give it with --out");
+                };
+                return html::write_site(&todo, dir);
+            }
+            let contents = export(&todo, *format)?;
+            match output {
+                Some(path) => write_file(path, &contents),
+                None => {
+                    print!("{contents}");
+                    Ok(())
+                }
+            }
+        }
+        Commands::Import { format, input } => {
+            let contents = match input {
+                Some(path) => fs::read_to_string(path).map_err(|e| Error::io(path, e))?,
+                None => {
+                    let mut contents = String::new();
+                    if let Err(e) = io::stdin().read_to_string(&mut contents) {
+                        return err!("Unable to read stdin: {e}");
+                    }
+                    contents
+                }
+            };
+            if !todo_file.exists() {
+                write_file(todo_file, "")?;
+            }
+            let mut todo = Todo::load_with(todo_file, &config)?;
+            let imported = import(&contents, *format, todo.today())?;
+            todo.merge(imported);
+            match todo.save() {
+                Ok(()) | Err(Error::UpToDate { .. }) => Ok(()),
+                Err(e) => Err(e),
+            }
+        }
+    }
+}
+
+fn export(todo: &Todo, format: FileFormat) -> Result<String> {
+    match format {
+        #[cfg(feature = "serde")]
+        FileFormat::Json => Ok(netxt::json::to_string(todo)? + "\n"),
+        #[cfg(not(feature = "serde"))]
+        FileFormat::Json => err!("JSON needs netxt to be built with the serde feature"),
+        FileFormat::Markdown => Ok(markdown::to_string(todo)),
+        FileFormat::Html => err!("Exporting html writes a directory, give it with --out"),
+        FileFormat::Ics => Ok(ics::to_string(todo)),
+        FileFormat::TodoTxt => Ok(todotxt::to_string(todo)),
+    }
+}
+
+fn import(contents: &str, format: FileFormat, today: NaiveDate) -> Result<Todo> {
+    match format {
+        #[cfg(feature = "serde")]
+        FileFormat::Json => netxt::json::from_str(contents),
+        #[cfg(not(feature = "serde"))]
+        FileFormat::Json => err!("JSON needs netxt to be built with the serde feature"),
+        FileFormat::Markdown => err!("Importing markdown is not supported"),
+        FileFormat::Html => err!("Importing html is not supported"),
+        FileFormat::Ics => err!("Importing ics is not supported"),
+        FileFormat::TodoTxt => Ok(todotxt::from_str(contents, today)),
+    }
+}
+
+fn write_file(path: &Path, contents: &str) -> Result<()> {
+    fs::write(path, contents).map_err(|e| Error::io(path, e))
+}
+
+/// Prints tasks under the day and section they are in
+fn print_tasks(tasks: &[(NaiveDate, &Section, &Task)], date_format: &str) {
+    let mut last: Option<(NaiveDate, &str)> = None;
+    for (date, section, task) in tasks {
+        if last.map(|(last_date, _)| last_date) != Some(*date) {
+            if last.is_some() {
+                println!();
+            }
+            println!("{}", date.format(date_format));
+            last = None;
+        }
+        if last.map(|(_, name)| name) != Some(section.name.as_str()) && !section.name.is_empty() {
+            println!("{}", section.name);
+        }
+        last = Some((*date, &section.name));
+        println!("{}{}", task.indent, task.line());
     }
 }
@@ -0,0 +1,135 @@
+//! Markdown export of todos
+//!
+//! Days are `## 2024-03-07` headings, newest first, sections are `###` headings and tasks are
+//! GitHub Flavored Markdown checklist items. Only done tasks are checked, other statuses are
+//! written after the task, e.g. `- [ ] deploy *(blocked)*`, and cancelled tasks are struck through.
+
+use crate::{
+    task::{Child, Task},
+    Day, Section, Status, Todo,
+};
+use itertools::Itertools;
+
+/// Markdown document with every day of todo
+pub fn to_string(todo: &Todo) -> String {
+    let blocks = todo
+        .days
+        .values()
+        .sorted_by(|a, b| b.date.cmp(&a.date))
+        .flat_map(day_blocks);
+    blocks.map(|block| block + "\n").join("\n")
+}
+
+/// Headings and lists of day, which are separated by blank lines
+fn day_blocks(day: &Day) -> Vec<String> {
+    let mut heading = format!("## {}", day.date.format("%Y-%m-%d"));
+    if day.planned {
+        heading.push_str(" (planned)");
+    }
+    let mut blocks = vec![heading];
+    for section in &day.sections {
+        blocks.extend(section_blocks(section));
+    }
+    blocks
+}
+
+fn section_blocks(section: &Section) -> Vec<String> {
+    let mut blocks = Vec::new();
+    // tasks of anonymous sections go right under the day
+    if !section.name.is_empty() {
+        blocks.push(format!("### {}", section.name.trim()));
+    }
+    if !section.tasks.is_empty() {
+        let mut list = String::new();
+        for task in &section.tasks {
+            format_task(&mut list, task, 0);
+        }
+        blocks.push(list.trim_end().to_string());
+    }
+    blocks
+}
+
+fn format_task(out: &mut String, task: &Task, depth: usize) {
+    let indent = "  ".repeat(depth);
+    let checkbox = match task.is_done() {
+        true => "x",
+        false => " ",
+    };
+    let text = match task.status {
+        Status::Cancelled => format!("~~{}~~", task.text),
+        _ => task.text.clone(),
+    };
+    let status = match task.status {
+        Status::Open | Status::Done | Status::Cancelled => String::new(),
+        status => format!(" *({status})*"),
+    };
+    out.push_str(&format!("{indent}- [{checkbox}] {text}{status}\n"));
+    // notes continue the item, a note after a subtask needs a blank line above it or it would
+    // continue the subtask instead
+    let mut after_subtask = false;
+    for child in task.children() {
+        match child {
+            Child::Note(note) if note.text.is_empty() => {}
+            Child::Note(note) => {
+                if after_subtask {
+                    out.push('\n');
+                }
+                out.push_str(&format!("{indent}  {}\n", note.text));
+                after_subtask = false;
+            }
+            Child::Subtask(subtask) => {
+                format_task(out, subtask, depth + 1);
+                after_subtask = true;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use indoc::indoc;
+
+    #[test]
+    fn markdown_export() {
+        let todo: Todo = indoc! {"
+            [2024-03-06]
+            - [x] groceries
+
+            [2024-03-07]
+            Work
+            - [!] deploy
+              waiting for review
+              - [x] build
+              - [-] benchmark
+              after the build
+              - docs
+            - review
+            Home
+        "}
+        .parse()
+        .unwrap();
+        let expected = indoc! {"
+            ## 2024-03-07
+
+            ### Work
+
+            - [ ] deploy *(blocked)*
+              waiting for review
+              - [x] build
+              - [ ] ~~benchmark~~
+
+              after the build
+              - [ ] docs
+            - [ ] review
+
+            ### Home
+
+            ## 2024-03-06
+
+            - [x] groceries
+        "};
+        assert_eq!(to_string(&todo), expected);
+    }
+}
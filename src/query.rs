@@ -0,0 +1,542 @@
+//! Small language for selecting tasks
+//!
+//! A query is made of filters combined with `and`, `or`, `not` and parentheses. `and` binds tighter
+//! than `or` and may be left out:
+//!
+//! ```text
+//! section:Work and not done and date>=2024-03-01 and text~"deploy"
+//! ```
+//!
+//! Filters:
+//! - `open`, `done`, `in-progress`, `blocked`, `cancelled`, `deferred` or `status:done`: tasks with
+//!   that status, `done` includes tasks whose subtasks are all done
+//! - `date:2024-03-01`, `date>=2024-03-01`, `date<today`: tasks in days on, before or after a date
+//! - `section:Work`: tasks in section Work, `section~work` for sections containing work in any case
+//! - `text:"deploy app"`, `text~deploy` or just `"deploy"`: tasks with that exact text, or containing it in any case
+//! - `tag:urgent` or `#urgent`: tasks tagged `#urgent`
+//! - `context:home` or `@home`: tasks with context `@home`
+//! - `due<=2024-05-01`, `est:2h`: tasks with a `key:value` property, values are compared as text
+//!
+//! `:` and `=` are the same, `<`, `<=`, `>` and `>=` work for dates and properties. `today` is the
+//! date given to [Query::parse], [crate::Todo::query] gives it the todo's today.
+
+use crate::{
+    err,
+    section::Section,
+    task::{Status, Task},
+    util::Result,
+};
+use chrono::NaiveDate;
+use std::{cmp::Ordering, fmt, str};
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum Query {
+    And(Box<Query>, Box<Query>),
+    Or(Box<Query>, Box<Query>),
+    Not(Box<Query>),
+    Status(Status),
+    Date(Comparison, NaiveDate),
+    Section(Match),
+    Text(Match),
+    Tag(String),
+    Context(String),
+    /// Property key, comparison and value
+    Property(String, Comparison, String),
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Comparison {
+    Equal,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum Match {
+    Exactly(String),
+    /// Contains the text, ignoring case
+    Contains(String),
+}
+
+impl Comparison {
+    fn holds<T: Ord + ?Sized>(&self, left: &T, right: &T) -> bool {
+        let ordering = left.cmp(right);
+        match self {
+            Comparison::Equal => ordering == Ordering::Equal,
+            Comparison::Less => ordering == Ordering::Less,
+            Comparison::LessOrEqual => ordering != Ordering::Greater,
+            Comparison::Greater => ordering == Ordering::Greater,
+            Comparison::GreaterOrEqual => ordering != Ordering::Less,
+        }
+    }
+}
+
+impl Match {
+    fn matches(&self, text: &str) -> bool {
+        match self {
+            Match::Exactly(expected) => text == expected,
+            Match::Contains(part) => text.to_lowercase().contains(&part.to_lowercase()),
+        }
+    }
+}
+
+impl Query {
+    /// Whether task, found in section of the day with date, is selected by the query
+    pub fn matches(&self, date: NaiveDate, section: &Section, task: &Task) -> bool {
+        match self {
+            Query::And(left, right) => {
+                left.matches(date, section, task) && right.matches(date, section, task)
+            }
+            Query::Or(left, right) => {
+                left.matches(date, section, task) || right.matches(date, section, task)
+            }
+            Query::Not(query) => !query.matches(date, section, task),
+            Query::Status(status) => task.effective_status() == *status,
+            Query::Date(comparison, expected) => comparison.holds(&date, expected),
+            Query::Section(name) => name.matches(&section.name),
+            Query::Text(text) => text.matches(&task.text),
+            Query::Tag(tag) => task.tags.contains(tag),
+            Query::Context(context) => task.contexts.contains(context),
+            Query::Property(key, comparison, expected) => task
+                .property(key)
+                .is_some_and(|value| comparison.holds(value, expected.as_str())),
+        }
+    }
+}
+
+impl Query {
+    /// Parses a query where `today` is the date today
+    pub fn parse(s: &str, today: NaiveDate) -> Result<Query> {
+        let mut parser = Parser {
+            tokens: tokenize(s)?,
+            index: 0,
+            end: s.chars().count() + 1,
+            today,
+        };
+        let query = parser.parse_or()?;
+        match parser.peek() {
+            Some(token) => invalid(parser.column(), format!("unexpected {token}")),
+            None => Ok(query),
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+enum Token {
+    Word(String),
+    Quoted(String),
+    Operator(Operator),
+    Open,
+    Close,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+enum Operator {
+    Is,
+    Contains,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Token::Word(word) => write!(f, "{word}"),
+            Token::Quoted(text) => write!(f, "\"{text}\""),
+            Token::Operator(operator) => write!(f, "{operator}"),
+            Token::Open => write!(f, "("),
+            Token::Close => write!(f, ")"),
+        }
+    }
+}
+
+impl fmt::Display for Operator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let symbol = match self {
+            Operator::Is => ":",
+            Operator::Contains => "~",
+            Operator::Less => "<",
+            Operator::LessOrEqual => "<=",
+            Operator::Greater => ">",
+            Operator::GreaterOrEqual => ">=",
+        };
+        write!(f, "{symbol}")
+    }
+}
+
+fn invalid<T>(column: usize, message: impl fmt::Display) -> Result<T> {
+    err!("Invalid query at column {column}: {message}")
+}
+
+fn cant_compare<T>(field: &str, (operator, column): (Operator, usize)) -> Result<T> {
+    invalid(column, format!("{field} can't be compared with {operator}"))
+}
+
+/// Splits a query into tokens and the column each one starts at
+fn tokenize(s: &str) -> Result<Vec<(Token, usize)>> {
+    let chars: Vec<char> = s.chars().collect();
+    let is_word_char = |c: char| !c.is_whitespace() && !"():=~<>\"".contains(c);
+
+    let mut tokens = Vec::new();
+    let mut index = 0;
+    while index < chars.len() {
+        let start = index;
+        let next_is_equal = chars.get(index + 1) == Some(&'=');
+        let token = match chars[index] {
+            c if c.is_whitespace() => {
+                index += 1;
+                continue;
+            }
+            '(' => Token::Open,
+            ')' => Token::Close,
+            ':' | '=' => Token::Operator(Operator::Is),
+            '~' => Token::Operator(Operator::Contains),
+            '<' if next_is_equal => Token::Operator(Operator::LessOrEqual),
+            '<' => Token::Operator(Operator::Less),
+            '>' if next_is_equal => Token::Operator(Operator::GreaterOrEqual),
+            '>' => Token::Operator(Operator::Greater),
+            '"' => {
+                let Some(len) = chars[index + 1..].iter().position(|c| *c == '"') else {
+                    return invalid(start + 1, "missing closing quote");
+                };
+                index += len + 1;
+                Token::Quoted(chars[start + 1..index].iter().collect())
+            }
+            _ => {
+                while index + 1 < chars.len() && is_word_char(chars[index + 1]) {
+                    index += 1;
+                }
+                Token::Word(chars[start..=index].iter().collect())
+            }
+        };
+        if matches!(
+            token,
+            Token::Operator(Operator::LessOrEqual | Operator::GreaterOrEqual)
+        ) {
+            index += 1;
+        }
+        index += 1;
+        tokens.push((token, start + 1));
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    index: usize,
+    /// Column right after the end of the query
+    end: usize,
+    /// Date `today` stands for
+    today: NaiveDate,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.index).map(|(token, _)| token)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.peek().cloned();
+        self.index += 1;
+        token
+    }
+
+    /// Column of the next token
+    fn column(&self) -> usize {
+        self.tokens
+            .get(self.index)
+            .map_or(self.end, |(_, column)| *column)
+    }
+
+    fn next_is_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Word(word)) if word.eq_ignore_ascii_case(keyword))
+    }
+
+    fn parse_or(&mut self) -> Result<Query> {
+        let mut query = self.parse_and()?;
+        while self.next_is_keyword("or") {
+            self.index += 1;
+            query = Query::Or(Box::new(query), Box::new(self.parse_and()?));
+        }
+        Ok(query)
+    }
+
+    fn parse_and(&mut self) -> Result<Query> {
+        let mut query = self.parse_not()?;
+        loop {
+            if self.next_is_keyword("and") {
+                self.index += 1;
+            } else if self.next_is_keyword("or") || matches!(self.peek(), Some(Token::Close) | None)
+            {
+                return Ok(query);
+            }
+            // filters next to each other are joined with and
+            query = Query::And(Box::new(query), Box::new(self.parse_not()?));
+        }
+    }
+
+    fn parse_not(&mut self) -> Result<Query> {
+        if self.next_is_keyword("not") {
+            self.index += 1;
+            return Ok(Query::Not(Box::new(self.parse_not()?)));
+        }
+        self.parse_filter()
+    }
+
+    fn parse_filter(&mut self) -> Result<Query> {
+        let column = self.column();
+        match self.next() {
+            Some(Token::Open) => {
+                let query = self.parse_or()?;
+                match self.peek() {
+                    Some(Token::Close) => {
+                        self.index += 1;
+                        Ok(query)
+                    }
+                    _ => invalid(self.column(), "expected )"),
+                }
+            }
+            Some(Token::Quoted(text)) => Ok(Query::Text(Match::Contains(text))),
+            Some(Token::Word(field)) => match self.peek() {
+                Some(Token::Operator(operator)) => {
+                    let operator = (*operator, self.column());
+                    self.index += 1;
+                    let value_column = self.column();
+                    match self.next() {
+                        Some(Token::Word(value) | Token::Quoted(value)) => {
+                            field_filter(&field, operator, (value, value_column), self.today)
+                        }
+                        _ => invalid(
+                            value_column,
+                            format!("expected a value after {field}{}", operator.0),
+                        ),
+                    }
+                }
+                _ => keyword_filter(&field, column),
+            },
+            Some(token) => invalid(column, format!("unexpected {token}")),
+            None => invalid(column, "expected a filter"),
+        }
+    }
+}
+
+/// Filter written as a single word e.g. `done` or `#tag`
+fn keyword_filter(word: &str, column: usize) -> Result<Query> {
+    if let Some(tag) = word.strip_prefix('#') {
+        return Ok(Query::Tag(tag.to_string()));
+    }
+    if let Some(context) = word.strip_prefix('@') {
+        return Ok(Query::Context(context.to_string()));
+    }
+    match word.to_lowercase().parse() {
+        Ok(status) => Ok(Query::Status(status)),
+        Err(_) => invalid(column, format!("unknown filter {word}")),
+    }
+}
+
+/// Filter written as `field`, operator and value e.g. `date>=2024-03-01`, with the column of each
+fn field_filter(
+    field: &str,
+    (operator, operator_column): (Operator, usize),
+    (value, column): (String, usize),
+    today: NaiveDate,
+) -> Result<Query> {
+    let text_match = || match operator {
+        Operator::Is => Ok(Match::Exactly(value.clone())),
+        Operator::Contains => Ok(Match::Contains(value.clone())),
+        _ => cant_compare(field, (operator, operator_column)),
+    };
+    let comparison = || match operator {
+        Operator::Is => Ok(Comparison::Equal),
+        Operator::Less => Ok(Comparison::Less),
+        Operator::LessOrEqual => Ok(Comparison::LessOrEqual),
+        Operator::Greater => Ok(Comparison::Greater),
+        Operator::GreaterOrEqual => Ok(Comparison::GreaterOrEqual),
+        Operator::Contains => cant_compare(field, (operator, operator_column)),
+    };
+    let is = || match operator {
+        Operator::Is => Ok(()),
+        _ => cant_compare(field, (operator, operator_column)),
+    };
+
+    match field {
+        "section" => Ok(Query::Section(text_match()?)),
+        "text" => Ok(Query::Text(text_match()?)),
+        "tag" => is().map(|_| Query::Tag(value.trim_start_matches('#').to_string())),
+        "context" => is().map(|_| Query::Context(value.trim_start_matches('@').to_string())),
+        "status" => {
+            is()?;
+            match value.to_lowercase().parse() {
+                Ok(status) => Ok(Query::Status(status)),
+                Err(_) => invalid(column, format!("unknown status {value}")),
+            }
+        }
+        "date" => {
+            let comparison = comparison()?;
+            let date = match value.as_str() {
+                "today" => today,
+                _ => match NaiveDate::parse_from_str(&value, "%Y-%m-%d") {
+                    Ok(date) => date,
+                    Err(_) => return invalid(column, format!("invalid date {value}")),
+                },
+            };
+            Ok(Query::Date(comparison, date))
+        }
+        key => Ok(Query::Property(key.to_string(), comparison()?, value)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::Todo;
+    use indoc::indoc;
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    fn parse(query: &str) -> Query {
+        Query::parse(query, date("2024-03-08")).unwrap()
+    }
+
+    #[test]
+    fn parse_query() {
+        let actual = parse(r#"section:Work and not done and date>=2024-03-01 and text~"deploy""#);
+        let expected = Query::And(
+            Box::new(Query::And(
+                Box::new(Query::And(
+                    Box::new(Query::Section(Match::Exactly("Work".to_string()))),
+                    Box::new(Query::Not(Box::new(Query::Status(Status::Done)))),
+                )),
+                Box::new(Query::Date(Comparison::GreaterOrEqual, date("2024-03-01"))),
+            )),
+            Box::new(Query::Text(Match::Contains("deploy".to_string()))),
+        );
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn parse_query_precedence() {
+        // and binds tighter than or, and can be left out
+        let actual = parse("#urgent or @home blocked");
+        let expected = Query::Or(
+            Box::new(Query::Tag("urgent".to_string())),
+            Box::new(Query::And(
+                Box::new(Query::Context("home".to_string())),
+                Box::new(Query::Status(Status::Blocked)),
+            )),
+        );
+        assert_eq!(actual, expected);
+
+        let actual = parse("(#urgent or @home) and due<2024-05-01");
+        let expected = Query::And(
+            Box::new(Query::Or(
+                Box::new(Query::Tag("urgent".to_string())),
+                Box::new(Query::Context("home".to_string())),
+            )),
+            Box::new(Query::Property(
+                "due".to_string(),
+                Comparison::Less,
+                "2024-05-01".to_string(),
+            )),
+        );
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn err_parse_query() {
+        let cases = [
+            ("", "Invalid query at column 1: expected a filter"),
+            ("done and", "Invalid query at column 9: expected a filter"),
+            ("(done", "Invalid query at column 6: expected )"),
+            ("done)", "Invalid query at column 5: unexpected )"),
+            (
+                "finished",
+                "Invalid query at column 1: unknown filter finished",
+            ),
+            (
+                "date>=2024-13-01",
+                "Invalid query at column 7: invalid date 2024-13-01",
+            ),
+            (
+                "section<Work",
+                "Invalid query at column 8: section can't be compared with <",
+            ),
+            (
+                "text~\"deploy",
+                "Invalid query at column 6: missing closing quote",
+            ),
+            (
+                "section:",
+                "Invalid query at column 9: expected a value after section:",
+            ),
+        ];
+        for (query, expected) in cases {
+            let actual = Query::parse(query, date("2024-03-08"))
+                .unwrap_err()
+                .to_string();
+            assert_eq!(actual, expected, "query: {query}");
+        }
+    }
+
+    #[test]
+    fn query_todo() {
+        let todo: Todo = indoc! {"
+            [2024-03-07]
+            Work
+            - deploy website #urgent
+            - [x] review PR
+            Home
+            - [!] fix sink @home
+            - clean up
+              - [x] kitchen
+              - [-] garage
+
+            [2024-02-28]
+            Work
+            - Deploy API due:2024-03-01
+        "}
+        .parse()
+        .unwrap();
+        let texts = |query: &str| -> Vec<String> {
+            let query = parse(query);
+            let mut texts = Vec::new();
+            for day in todo.days.values() {
+                for section in &day.sections {
+                    for task in section.iter_tasks() {
+                        if query.matches(day.date, section, task) {
+                            texts.push(task.text.clone());
+                        }
+                    }
+                }
+            }
+            texts.sort();
+            texts
+        };
+
+        assert_eq!(
+            texts(r#"section:Work and not done and date>=2024-03-01 and text~"deploy""#),
+            vec!["deploy website #urgent"]
+        );
+        assert_eq!(
+            texts("text~deploy"),
+            vec!["Deploy API due:2024-03-01", "deploy website #urgent"]
+        );
+        assert_eq!(texts("done"), vec!["clean up", "kitchen", "review PR"]);
+        assert_eq!(texts("open and section:Home"), Vec::<String>::new());
+        assert_eq!(
+            texts("blocked or #urgent"),
+            vec!["deploy website #urgent", "fix sink @home"]
+        );
+        assert_eq!(texts("due<=2024-03-01"), vec!["Deploy API due:2024-03-01"]);
+        assert_eq!(texts("date<2024-03-07 or status:cancelled").len(), 2);
+        assert_eq!(texts("date<today"), texts("date<=2024-03-07"));
+        assert_eq!(texts("date>=today"), Vec::<String>::new());
+    }
+}
@@ -0,0 +1,269 @@
+//! What a new day starts with
+//!
+//! The policy is read from the `[rollover]` table of the user's config, see [crate::config], or of a
+//! `.netxt.toml` file next to the todo file, which takes precedence over the user's config but not
+//! over environment variables and flags:
+//!
+//! ```toml
+//! [rollover]
+//! carry = "open"          # "all" (default), "not-done" or "open"
+//! clear = ["Done"]        # sections that start empty
+//! pinned = ["Ideas"]      # sections copied as they are
+//! template = ["Done"]     # sections every day starts with
+//! ```
+
+use crate::{
+    day::Day,
+    err,
+    task::{Status, Task},
+    util::Result,
+    Error,
+};
+use chrono::NaiveDate;
+use serde::Deserialize;
+use std::{fmt, fs::read_to_string, io, path::Path, str};
+
+/// Name of the file next to a todo file that configures it
+pub static CONFIG_FILE: &str = ".netxt.toml";
+
+/// Which tasks of the last day are copied to a new day
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Carry {
+    /// Every task, as they are
+    #[default]
+    All,
+    /// Tasks that are not done
+    NotDone,
+    /// Tasks that are neither done nor cancelled
+    Open,
+}
+
+impl Carry {
+    pub const ALL: [Carry; 3] = [Carry::All, Carry::NotDone, Carry::Open];
+
+    /// Name of the policy as written in config files e.g. `not-done`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Carry::All => "all",
+            Carry::NotDone => "not-done",
+            Carry::Open => "open",
+        }
+    }
+}
+
+impl fmt::Display for Carry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl str::FromStr for Carry {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self> {
+        match Carry::ALL.into_iter().find(|carry| carry.name() == s) {
+            Some(carry) => Ok(carry),
+            None => err!("Unknown carry policy: {s}, expected all, not-done or open"),
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Rollover {
+    pub carry: Carry,
+    /// Sections that are kept but start without tasks
+    pub clear: Vec<String>,
+    /// Sections copied with all of their tasks, whatever carry and clear say
+    pub pinned: Vec<String>,
+    /// Sections every new day has, added empty at the end when the last day doesn't have them
+    pub template: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct FileConfig {
+    rollover: Option<Rollover>,
+}
+
+impl Rollover {
+    /// Policy configured next to todo_file, if any
+    pub fn for_file(todo_file: &Path) -> Result<Option<Rollover>> {
+        let parent = todo_file.parent().unwrap_or(Path::new(""));
+        let path = parent.join(CONFIG_FILE);
+        let contents = match read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(Error::io(&path, e)),
+        };
+        match toml::from_str::<FileConfig>(&contents) {
+            Ok(config) => Ok(config.rollover),
+            Err(e) => err!("Invalid config {}: {}", path.display(), e.message()),
+        }
+    }
+
+    /// New day with date, built from the last day before it if there's one
+    pub fn roll(&self, last_day: Option<&Day>, date: NaiveDate) -> Day {
+        let mut day = Day::new(date);
+        for section in last_day.iter().flat_map(|last_day| &last_day.sections) {
+            let mut section = section.clone();
+            if !self.pinned.contains(&section.name) {
+                match self.clear.contains(&section.name) {
+                    true => section.tasks.clear(),
+                    false => section.tasks = self.carry_tasks(section.tasks),
+                }
+            }
+            day.sections.push(section);
+        }
+        for name in &self.template {
+            day.section_mut(name);
+        }
+        day
+    }
+
+    fn carry_tasks(&self, tasks: Vec<Task>) -> Vec<Task> {
+        tasks
+            .into_iter()
+            .filter(|task| match self.carry {
+                Carry::All => true,
+                Carry::NotDone => !task.is_done(),
+                Carry::Open => !task.is_done() && task.status != Status::Cancelled,
+            })
+            .map(|mut task| {
+                task.subtasks = self.carry_tasks(task.subtasks);
+                task
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::{
+        config::{Config, Overrides},
+        Todo,
+    };
+    use indoc::indoc;
+    use std::fs;
+    use tempfile::tempdir;
+
+    fn last_day() -> Day {
+        indoc! {"
+            [2024-03-06]
+            Work
+            - [x] deploy
+            - review
+              - [x] part 1
+              - [-] part 2
+              - part 3
+            - [-] meeting
+            Ideas
+            - [x] blog post
+            Done
+            - [x] groceries
+        "}
+        .parse()
+        .unwrap()
+    }
+
+    fn date() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 3, 7).unwrap()
+    }
+
+    #[test]
+    fn default_copies_last_day() {
+        let day = Rollover::default().roll(Some(&last_day()), date());
+        assert_eq!(day.date, date());
+        assert_eq!(day.sections, last_day().sections);
+
+        let day = Rollover::default().roll(None, date());
+        assert_eq!(day, Day::new(date()));
+    }
+
+    #[test]
+    fn roll_with_policy() {
+        let rollover = Rollover {
+            carry: Carry::Open,
+            clear: vec!["Done".to_string()],
+            pinned: vec!["Ideas".to_string()],
+            template: vec!["Done".to_string(), "Notes".to_string()],
+        };
+        let expected = indoc! {"
+            [2024-03-07]
+            Work
+            - review
+              - part 3
+
+            Ideas
+            - [x] blog post
+
+            Done
+
+
+            Notes
+        "};
+        let day = rollover.roll(Some(&last_day()), date());
+        assert_eq!(day.to_string(), expected);
+
+        let rollover = Rollover {
+            carry: Carry::NotDone,
+            ..Default::default()
+        };
+        let day = rollover.roll(Some(&last_day()), date());
+        let texts: Vec<&str> = day.sections[0]
+            .iter_tasks()
+            .map(|task| task.text.as_str())
+            .collect();
+        assert_eq!(texts, vec!["review", "part 2", "part 3", "meeting"]);
+    }
+
+    #[test]
+    fn read_rollover_config() {
+        let dir = tempdir().unwrap();
+        let todo_file = dir.path().join("todo.txt");
+        assert_eq!(Rollover::for_file(&todo_file).unwrap(), None);
+
+        let config = indoc! {r#"
+            [rollover]
+            carry = "not-done"
+            clear = ["Done"]
+        "#};
+        fs::write(dir.path().join(CONFIG_FILE), config).unwrap();
+        let expected = Rollover {
+            carry: Carry::NotDone,
+            clear: vec!["Done".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(Rollover::for_file(&todo_file).unwrap(), Some(expected));
+
+        fs::write(
+            dir.path().join(CONFIG_FILE),
+            "[rollover]\ncarry = \"some\"\n",
+        )
+        .unwrap();
+        assert!(Rollover::for_file(&todo_file).is_err());
+    }
+
+    #[test]
+    fn overrides_win_over_rollover_config() {
+        let dir = tempdir().unwrap();
+        let todo_file = dir.path().join("todo.txt");
+        fs::write(&todo_file, "[2024-03-06]\n- task\n").unwrap();
+        let config = indoc! {r#"
+            [rollover]
+            carry = "not-done"
+            clear = ["Done"]
+        "#};
+        fs::write(dir.path().join(CONFIG_FILE), config).unwrap();
+
+        let overrides = Overrides {
+            carry: Some(Carry::Open),
+            ..Default::default()
+        };
+        let config = Config::default().with_overrides(overrides).unwrap();
+        let todo = Todo::load_with(&todo_file, &config).unwrap();
+        assert_eq!(todo.config.rollover.carry, Carry::Open);
+        assert_eq!(todo.config.rollover.clear, vec!["Done"]);
+    }
+}
@@ -0,0 +1,116 @@
+//! Full-text search over tasks
+
+use crate::{section::Section, task::Task, util::Result, Error};
+use chrono::NaiveDate;
+use regex::{Regex, RegexBuilder};
+
+/// How a search pattern is matched, by default it is plain text that is case sensitive
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub struct SearchOptions {
+    /// Pattern is a regular expression
+    pub regex: bool,
+    pub ignore_case: bool,
+    /// Only match whole words
+    pub whole_word: bool,
+}
+
+/// Compiled search pattern
+#[derive(Debug, Clone)]
+pub struct Pattern {
+    regex: Regex,
+}
+
+impl Pattern {
+    pub fn new(pattern: &str, options: SearchOptions) -> Result<Pattern> {
+        let mut pattern = match options.regex {
+            true => pattern.to_string(),
+            false => regex::escape(pattern),
+        };
+        if options.whole_word {
+            pattern = format!(r"\b(?:{pattern})\b");
+        }
+        let regex = RegexBuilder::new(&pattern)
+            .case_insensitive(options.ignore_case)
+            .build()
+            .map_err(|e| Error::Message(format!("Invalid search pattern: {e}")))?;
+        Ok(Pattern { regex })
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        self.regex.is_match(text)
+    }
+
+    /// Whether the pattern is found in the text or in one of the notes of task
+    pub fn matches_task(&self, task: &Task) -> bool {
+        self.is_match(&task.text) || task.notes.iter().any(|note| self.is_match(&note.text))
+    }
+}
+
+/// Iterator over the tasks (and subtasks) matching a pattern, see [crate::Todo::search]
+pub struct SearchIterator<'a, I> {
+    tasks: I,
+    pattern: &'a Pattern,
+}
+
+impl<'a, I> SearchIterator<'a, I> {
+    pub(crate) fn new(tasks: I, pattern: &'a Pattern) -> Self {
+        SearchIterator { tasks, pattern }
+    }
+}
+
+impl<'a, I> Iterator for SearchIterator<'a, I>
+where
+    I: Iterator<Item = (NaiveDate, &'a Section, &'a Task)>,
+{
+    type Item = (NaiveDate, &'a Section, &'a Task);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.tasks
+            .find(|(_, _, task)| self.pattern.matches_task(task))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn match_pattern() {
+        let text = "Deploy the deployment";
+        let options = SearchOptions::default();
+        assert!(Pattern::new("deploy", options).unwrap().is_match(text));
+        assert!(!Pattern::new("DEPLOY", options).unwrap().is_match(text));
+        // without regex special characters are plain text
+        assert!(!Pattern::new("dep.oy", options).unwrap().is_match(text));
+
+        let options = SearchOptions {
+            ignore_case: true,
+            ..Default::default()
+        };
+        assert!(Pattern::new("DEPLOY", options).unwrap().is_match(text));
+
+        let options = SearchOptions {
+            regex: true,
+            ..Default::default()
+        };
+        assert!(Pattern::new("dep.oy", options).unwrap().is_match(text));
+        assert!(Pattern::new("dep(", options).is_err());
+
+        let options = SearchOptions {
+            whole_word: true,
+            ..Default::default()
+        };
+        assert!(!Pattern::new("deploy", options).unwrap().is_match(text));
+        assert!(Pattern::new("deployment", options).unwrap().is_match(text));
+    }
+
+    #[test]
+    fn match_task_notes() {
+        let pattern = Pattern::new("logs", SearchOptions::default()).unwrap();
+        let mut task = Task::new("deploy");
+        assert!(!pattern.matches_task(&task));
+        task.add_note("check the logs");
+        assert!(pattern.matches_task(&task));
+    }
+}
@@ -1,10 +1,15 @@
+use crate::address::siblings_mut;
 use crate::err;
-use crate::task::Task;
+use crate::error::Error;
+use crate::task::{Status, Task};
 use crate::util::Result;
 
 use itertools::Itertools;
-use std::error;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fmt;
+use std::ops::Range;
 use std::str;
 
 pub struct SectionIterator<'a> {
@@ -25,39 +30,41 @@ impl<'a> Iterator for SectionIterator<'a> {
     type Item = Result<Section>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let mut section: Vec<&'a str> = Vec::new();
-        if self.index >= self.lines.len() {
-            None
-        } else {
-            // first line must be start of a section, consume anything that is not start of section
-            while self.index < self.lines.len() && !is_section_start(self.lines[self.index]) {
-                self.index += 1;
-            }
-            if self.index >= self.lines.len() {
-                return None;
-            }
+        let lines = next_section_lines(&self.lines, self.index)?;
+        self.index = lines.end;
 
-            // read start of section
-            let start = self.lines[self.index];
-            section.push(start);
-            self.index += 1;
+        let section = self.lines[lines.clone()].join("\n").parse();
+        Some(section.map_err(|e: Error| e.offset_lines(lines.start)))
+    }
+}
 
-            // read everything until start of next section
-            while self.index < self.lines.len() && !is_section_end(self.lines[self.index]) {
-                section.push(self.lines[self.index]);
-                self.index += 1;
-            }
+/// Range of lines of the first section at or after index, lines before the start of a section are skipped
+pub(crate) fn next_section_lines(lines: &[&str], mut index: usize) -> Option<Range<usize>> {
+    // first line must be start of a section, consume anything that is not start of section
+    while index < lines.len() && !is_section_start(lines[index]) {
+        index += 1;
+    }
+    if index >= lines.len() {
+        return None;
+    }
 
-            Some(section.join("\n").parse())
-        }
+    // read start of section
+    let start = index;
+    index += 1;
+
+    // read everything until start of next section
+    while index < lines.len() && !is_section_end(lines[index]) {
+        index += 1;
     }
+    Some(start..index)
 }
 
 fn is_section_start(line: &str) -> bool {
     if line.trim().is_empty() {
         return false;
     }
-    let first_char = line.chars().next();
+    // indented tasks are subtasks
+    let first_char = line.trim_start().chars().next();
     match first_char {
         Some('-') => true, // tasks start anonymous sections
         Some('[') => false,
@@ -70,18 +77,19 @@ fn is_section_end(line: &str) -> bool {
     if line.trim().is_empty() {
         return false;
     }
-    let first_char = line.chars().next();
-    match first_char {
-        Some('-') => false,
-        Some('[') => false,
-        None => false,
-        _ => true,
+    // indented lines are task notes
+    if line.starts_with([' ', '\t']) {
+        return false;
     }
+    let first_char = line.trim_start().chars().next();
+    !matches!(first_char, Some('-') | Some('[') | None)
 }
 
 #[derive(PartialEq, Eq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Section {
     pub name: String,
+    #[cfg_attr(feature = "serde", serde(default))]
     pub tasks: Vec<Task>,
 }
 
@@ -92,44 +100,132 @@ impl Section {
             tasks: Vec::<Task>::new(),
         }
     }
+
+    /// Iterates over all tasks in this section including subtasks, depth first
+    pub fn iter_tasks(&self) -> impl Iterator<Item = &Task> {
+        self.tasks.iter().flat_map(|task| task.iter())
+    }
+
+    /// First task (or subtask) with the given text that is not done yet
+    pub fn find_open_task_mut(&mut self, text: &str) -> Option<&mut Task> {
+        find_open_task_mut(&mut self.tasks, text)
+    }
+
+    /// Removes the task (or subtask) at position, positions start at 1 as in task addresses
+    pub fn remove_task(&mut self, position: &[usize]) -> Option<Task> {
+        let (tasks, index) = siblings_mut(&mut self.tasks, position)?;
+        Some(tasks.remove(index))
+    }
+
+    /// Moves the task at position to another place among the tasks next to it, starting at 1
+    ///
+    /// Places past the last task move it to the end. Returns false if there is no task at position.
+    pub fn reorder_task(&mut self, position: &[usize], to: usize) -> bool {
+        let Some((tasks, index)) = siblings_mut(&mut self.tasks, position) else {
+            return false;
+        };
+        let task = tasks.remove(index);
+        let to = to.saturating_sub(1).min(tasks.len());
+        tasks.insert(to, task);
+        true
+    }
+
+    /// Marks tasks whose subtasks are all done as done
+    pub fn roll_up(&mut self) {
+        for task in &mut self.tasks {
+            task.roll_up();
+        }
+    }
+
+    /// Number of tasks (and subtasks) in this section with the given status, parents whose subtasks
+    /// are done count as done
+    pub fn count(&self, status: Status) -> usize {
+        self.iter_tasks()
+            .filter(|task| task.effective_status() == status)
+            .count()
+    }
+
+    /// Number of tasks (and subtasks) in this section for each status, statuses without tasks are left out
+    pub fn count_by_status(&self) -> HashMap<Status, usize> {
+        let mut counts = HashMap::new();
+        for task in self.iter_tasks() {
+            *counts.entry(task.effective_status()).or_insert(0) += 1;
+        }
+        counts
+    }
+}
+
+fn find_open_task_mut<'a>(tasks: &'a mut [Task], text: &str) -> Option<&'a mut Task> {
+    for task in tasks {
+        if task.text == text && !task.is_done() {
+            return Some(task);
+        }
+        if let Some(subtask) = find_open_task_mut(&mut task.subtasks, text) {
+            return Some(subtask);
+        }
+    }
+    None
 }
 
 impl str::FromStr for Section {
-    type Err = Box<dyn error::Error + Send + Sync>;
+    type Err = Error;
     fn from_str(s: &str) -> Result<Self> {
-        let text = s.trim().to_string();
+        let (section, errors) = Section::parse_lenient(s)?;
+        match errors.into_iter().next() {
+            Some(e) => Err(e),
+            None => Ok(section),
+        }
+    }
+}
+
+impl Section {
+    /// Parses a section skipping lines that are neither tasks nor notes, returns the errors for those lines
+    pub(crate) fn parse_lenient(s: &str) -> Result<(Section, Vec<Error>)> {
+        // line numbers are kept so errors can point at the line that failed
+        let mut lines = s.lines().enumerate();
 
         // first line may be the section name
         // if first line is task, it means it's an anonymous section
-        let mut lines = text.lines();
-        let first_line = lines
-            .next()
-            .expect("Unable to read section name")
-            .trim()
-            .to_string();
+        let Some((first_index, first_line)) = lines.find(|(_, line)| !line.trim().is_empty())
+        else {
+            return err!("Unable to read section name");
+        };
 
         let mut tasks: Vec<Task> = Vec::new();
+        let mut errors: Vec<Error> = Vec::new();
 
-        let first_char = first_line.chars().next();
-        let name = match first_char {
+        let first_line = first_line.trim();
+        let name = match first_line.chars().next() {
             Some('-') => {
                 // tasks start anonymous sections
                 // if first line is a task this means this section is anonymous
-                tasks.push(first_line.parse().expect("Unable to parse task"));
+                let task: Task = first_line
+                    .parse()
+                    .map_err(|e: Error| e.offset_lines(first_index))?;
+                tasks.push(task);
                 "".to_string()
             }
-            _ => first_line,
+            _ => first_line.to_string(),
         };
 
-        // the rest of the lines should be tasks
-        for line in lines {
-            let task: Task = match line.parse() {
-                Ok(t) => t,
-                Err(e) => return err!("Unable to parse section {name}: {e}"),
-            };
-            tasks.push(task);
+        // the rest of the lines should be tasks or notes of the task above them
+        // blank lines are kept only between notes
+        let mut blank_lines = 0;
+        for (index, line) in lines {
+            if line.trim().is_empty() {
+                blank_lines += 1;
+                continue;
+            }
+            let blanks = std::mem::take(&mut blank_lines);
+            if !line.trim_start().starts_with('-') && Task::attach_note(&mut tasks, line, blanks) {
+                continue;
+            }
+            match line.parse() {
+                Ok(task) => Task::insert_nested(&mut tasks, task),
+                Err(e) => errors.push(e.offset_lines(index)),
+            }
         }
-        Ok(Section { name, tasks })
+        Ok((Section { name, tasks }, errors))
     }
 }
 
@@ -145,6 +241,7 @@ impl fmt::Display for Section {
 mod tests {
 
     use super::*;
+    use crate::task::Note;
     use indoc::indoc;
 
     #[test]
@@ -161,20 +258,175 @@ mod tests {
         let expected = Section {
             name: "Some Section".to_string(),
             tasks: vec![
-                Task {
-                    text: "task 1".to_string(),
-                },
-                Task {
-                    text: "task 3".to_string(),
-                },
-                Task {
-                    text: "task 2".to_string(),
-                },
+                Task::new("task 1"),
+                Task::new("task 3"),
+                Task::new("task 2"),
             ],
         };
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn count_section_tasks() {
+        let section_text = indoc! {"
+            Some Section
+            - [x] task 1
+            - [!] task 2
+            - task 3
+            - [ ] task 4
+            - [x] task 5
+            - [!] task 6
+              - [x] step
+        "};
+
+        // task 6 is done because of its subtask
+        let section: Section = section_text.parse().expect("Unable to parse section");
+        assert_eq!(section.count(Status::Done), 4);
+        assert_eq!(section.count(Status::Open), 2);
+        assert_eq!(section.count(Status::Cancelled), 0);
+
+        let expected = HashMap::from([(Status::Done, 4), (Status::Open, 2), (Status::Blocked, 1)]);
+        assert_eq!(section.count_by_status(), expected);
+    }
+
+    #[test]
+    fn parse_section_subtasks() {
+        let section_text = indoc! {"
+            Some Section
+            - task 1
+              - task 1.1
+                - task 1.1.1
+              - task 1.2
+            - task 2
+            	- task 2.1
+        "};
+
+        let actual: Section = section_text.parse().expect("Unable to parse section");
+        assert_eq!(actual.tasks.len(), 2);
+        assert_eq!(actual.tasks[0].subtasks.len(), 2);
+        assert_eq!(actual.tasks[0].subtasks[0].subtasks[0].text, "task 1.1.1");
+        assert_eq!(actual.tasks[1].subtasks[0].text, "task 2.1");
+        assert_eq!(actual.iter_tasks().count(), 6);
+
+        // indentation is written back
+        assert_eq!(actual.to_string(), section_text.trim_end());
+    }
+
+    #[test]
+    fn parse_section_notes() {
+        let section_text = indoc! {"
+            Some Section
+            - deploy
+              run this first:
+              cargo build --release
+              - check logs
+                https://example.com/logs
+            - task 2
+        "};
+
+        let actual: Section = section_text.parse().expect("Unable to parse section");
+        let notes = |task: &Task| {
+            task.notes
+                .iter()
+                .map(|note| note.text.clone())
+                .collect_vec()
+        };
+        assert_eq!(
+            notes(&actual.tasks[0]),
+            vec!["run this first:", "cargo build --release"]
+        );
+        assert_eq!(
+            notes(&actual.tasks[0].subtasks[0]),
+            vec!["https://example.com/logs"]
+        );
+        assert!(actual.tasks[1].notes.is_empty());
+
+        // notes survive a round-trip
+        assert_eq!(actual.to_string(), section_text.trim_end());
+    }
+
+    #[test]
+    fn section_notes_roundtrip() {
+        // indentation of pasted code, blank lines between paragraphs and notes after subtasks
+        let section_text = indoc! {"
+            Some Section
+            - deploy
+              run this first:
+                  if ! cargo test; then
+                      exit 1
+                  fi
+
+              then check the logs
+              - build
+              [docs](https://example.com/docs)
+            - task 2
+        "};
+
+        let actual: Section = section_text.parse().expect("Unable to parse section");
+        let deploy = &actual.tasks[0];
+        assert_eq!(deploy.notes[1].indent, "      ");
+        assert_eq!(deploy.notes[4], Note::default());
+        assert_eq!(deploy.notes[5].position, 0);
+        assert_eq!(deploy.notes[6].position, 1);
+        assert_eq!(actual.to_string(), section_text.trim_end());
+    }
+
+    #[test]
+    fn remove_and_reorder_tasks() {
+        let section_text = indoc! {"
+            Some Section
+            - task 1
+              - task 1.1
+              - task 1.2
+            - task 2
+            - task 3
+        "};
+        let mut section: Section = section_text.parse().expect("Unable to parse section");
+
+        assert!(section.reorder_task(&[3], 1));
+        assert!(section.reorder_task(&[2, 2], 1));
+        assert!(section.reorder_task(&[2], 10));
+        assert!(!section.reorder_task(&[4], 1));
+        let expected = indoc! {"
+            Some Section
+            - task 3
+            - task 2
+            - task 1
+              - task 1.2
+              - task 1.1"};
+        assert_eq!(section.to_string(), expected);
+
+        let removed = section.remove_task(&[3, 1]).map(|task| task.text);
+        assert_eq!(removed, Some("task 1.2".to_string()));
+        let removed = section.remove_task(&[1]).map(|task| task.text);
+        assert_eq!(removed, Some("task 3".to_string()));
+        assert_eq!(section.remove_task(&[5]), None);
+        assert_eq!(section.iter_tasks().count(), 3);
+    }
+
+    #[test]
+    fn parse_section_lenient() {
+        let section_text = indoc! {"
+            Some Section
+            - task 1
+            whoops
+            - task 2
+            also wrong
+        "};
+
+        let (section, errors) = Section::parse_lenient(section_text).unwrap();
+        assert_eq!(
+            section.tasks,
+            vec![Task::new("task 1"), Task::new("task 2")]
+        );
+        let lines: Vec<usize> = errors
+            .iter()
+            .filter_map(|e| e.location())
+            .map(|location| location.line)
+            .collect();
+        assert_eq!(lines, vec![3, 5]);
+    }
+
     #[test]
     fn err_parse_section() {
         let section_text = indoc! {"
@@ -186,10 +438,8 @@ mod tests {
 
         "};
 
-        let name = "Some Section";
         let line = "whoops other section";
-        let expected_error =
-            format!("Unable to parse section {name}: Unable to parse task: {line}");
+        let expected_error = format!("line 4, column 1: Unable to parse task: {line}");
         let actual: Result<Section> = section_text.parse();
 
         match actual {
@@ -1,34 +1,486 @@
 use crate::err;
+use crate::error::{Error, Location};
 use crate::util::Result;
-use std::error;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fmt;
 use std::str::FromStr;
 
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Hash, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum Status {
+    #[default]
+    Open,
+    Done,
+    InProgress,
+    Blocked,
+    Cancelled,
+    Deferred,
+}
+
+impl Status {
+    pub const ALL: [Status; 6] = [
+        Status::Open,
+        Status::Done,
+        Status::InProgress,
+        Status::Blocked,
+        Status::Cancelled,
+        Status::Deferred,
+    ];
+
+    /// Name of the status e.g. `in-progress`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Status::Open => "open",
+            Status::Done => "done",
+            Status::InProgress => "in-progress",
+            Status::Blocked => "blocked",
+            Status::Cancelled => "cancelled",
+            Status::Deferred => "deferred",
+        }
+    }
+
+    /// Parses the marker found between the checkbox brackets e.g. `x` in `- [x]`
+    fn from_marker(marker: char) -> Option<Status> {
+        match marker {
+            ' ' => Some(Status::Open),
+            'x' | 'X' => Some(Status::Done),
+            '~' => Some(Status::InProgress),
+            '!' => Some(Status::Blocked),
+            '-' => Some(Status::Cancelled),
+            '>' => Some(Status::Deferred),
+            _ => None,
+        }
+    }
+
+    fn marker(&self) -> char {
+        match self {
+            Status::Open => ' ',
+            Status::Done => 'x',
+            Status::InProgress => '~',
+            Status::Blocked => '!',
+            Status::Cancelled => '-',
+            Status::Deferred => '>',
+        }
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for Status {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self> {
+        match Status::ALL.into_iter().find(|status| status.name() == s) {
+            Some(status) => Ok(status),
+            None => err!("Unknown status: {s}"),
+        }
+    }
+}
+
 #[derive(PartialEq, Eq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(default))]
 pub struct Task {
     pub text: String,
+    pub status: Status,
+    /// Whether the task is written with a checkbox (`- [ ] task`) or as a plain dash (`- task`)
+    pub checkbox: bool,
+    /// Checkbox marker as written when it is another spelling of the status marker e.g. `X` for `x`
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub marker: Option<char>,
+    /// `#tags` found in text, without the `#`
+    pub tags: Vec<String>,
+    /// `@contexts` found in text, without the `@`
+    pub contexts: Vec<String>,
+    /// `key:value` pairs found in text e.g. `due:2024-05-01`
+    pub properties: BTreeMap<String, String>,
+    /// Whitespace before the dash, deeper indentation than the task above makes a subtask
+    pub indent: String,
+    pub subtasks: Vec<Task>,
+    /// Indented lines without a dash below the task
+    pub notes: Vec<Note>,
+}
+
+/// Indented line without a dash below a task, or a blank line between two of them
+#[derive(PartialEq, Eq, Debug, Clone, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(from = "NoteDocument")
+)]
+pub struct Note {
+    /// Line without its indentation, empty for blank lines
+    pub text: String,
+    /// Whitespace before the text as written, so that pasted code keeps its shape
+    pub indent: String,
+    /// Number of subtasks above the note
+    pub position: usize,
+}
+
+impl Note {
+    pub fn new(text: &str) -> Note {
+        Note {
+            text: text.to_string(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Note or subtask of a task
+pub(crate) enum Child<'a> {
+    Note(&'a Note),
+    Subtask(&'a Task),
+}
+
+/// Notes are imported from their text alone or with all of their fields
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum NoteDocument {
+    Text(String),
+    Note {
+        text: String,
+        #[serde(default)]
+        indent: String,
+        #[serde(default)]
+        position: usize,
+    },
+}
+
+#[cfg(feature = "serde")]
+impl From<NoteDocument> for Note {
+    fn from(note: NoteDocument) -> Self {
+        match note {
+            NoteDocument::Text(text) => Note::new(&text),
+            NoteDocument::Note {
+                text,
+                indent,
+                position,
+            } => Note {
+                text,
+                indent,
+                position,
+            },
+        }
+    }
+}
+
+impl Task {
+    pub fn new(text: &str) -> Task {
+        let mut task = Task {
+            text: String::new(),
+            status: Status::Open,
+            checkbox: false,
+            marker: None,
+            tags: Vec::new(),
+            contexts: Vec::new(),
+            properties: BTreeMap::new(),
+            indent: String::new(),
+            subtasks: Vec::new(),
+            notes: Vec::new(),
+        };
+        task.set_text(text);
+        task
+    }
+
+    /// Replaces the task text and extracts tags, contexts and properties from it again
+    pub fn set_text(&mut self, text: &str) {
+        self.text = text.to_string();
+        self.tags.clear();
+        self.contexts.clear();
+        self.properties.clear();
+
+        for word in text.split_whitespace() {
+            if let Some(tag) = word.strip_prefix('#').and_then(metadata_name) {
+                self.tags.push(tag.to_string());
+            } else if let Some(context) = word.strip_prefix('@').and_then(metadata_name) {
+                self.contexts.push(context.to_string());
+            } else if let Some((key, value)) = parse_property(word) {
+                self.properties.insert(key.to_string(), value.to_string());
+            }
+        }
+    }
+
+    pub fn property(&self, key: &str) -> Option<&str> {
+        self.properties.get(key).map(|value| value.as_str())
+    }
+
+    /// A task is done when marked as done or when its subtasks are done or cancelled, with at least
+    /// one of them done
+    pub fn is_done(&self) -> bool {
+        self.status == Status::Done
+            || (self.subtasks.iter().any(|task| task.is_done())
+                && self
+                    .subtasks
+                    .iter()
+                    .all(|task| task.is_done() || task.status == Status::Cancelled))
+    }
+
+    /// Status of the task with subtasks rolled up, `Done` whenever [Task::is_done]
+    pub fn effective_status(&self) -> Status {
+        match self.is_done() {
+            true => Status::Done,
+            false => self.status,
+        }
+    }
+
+    pub fn complete(&mut self) {
+        self.set_status(Status::Done);
+    }
+
+    pub fn set_status(&mut self, status: Status) {
+        self.status = status;
+        self.checkbox = true;
+        self.marker = None;
+    }
+
+    /// Marks this task and its subtasks as done when all of their subtasks are done
+    pub fn roll_up(&mut self) {
+        for task in &mut self.subtasks {
+            task.roll_up();
+        }
+        if self.status != Status::Done && self.is_done() {
+            self.complete();
+        }
+    }
+
+    /// Task line without indentation, notes and subtasks e.g. `- [x] deploy`
+    pub fn line(&self) -> String {
+        let text = &self.text;
+        if self.checkbox || self.status != Status::Open {
+            let marker = self.marker.unwrap_or(self.status.marker());
+            format!("- [{marker}] {text}")
+        } else {
+            format!("- {text}")
+        }
+    }
+
+    /// Iterates over this task and all of its subtasks, depth first
+    pub fn iter(&self) -> Box<dyn Iterator<Item = &Task> + '_> {
+        Box::new(std::iter::once(self).chain(self.subtasks.iter().flat_map(|task| task.iter())))
+    }
+
+    /// Notes and subtasks in the order they are written, notes go between the subtasks they were
+    /// written between
+    pub(crate) fn children(&self) -> impl Iterator<Item = Child<'_>> {
+        let count = self.subtasks.len();
+        (0..=count).flat_map(move |position| {
+            let notes = self
+                .notes
+                .iter()
+                .filter(move |note| note.position.min(count) == position)
+                .map(Child::Note);
+            notes.chain(self.subtasks.get(position).map(Child::Subtask))
+        })
+    }
+
+    /// Line of one of the notes of this task, empty for blank lines
+    pub(crate) fn note_line(&self, note: &Note) -> String {
+        let indent = &self.indent;
+        match note.text.is_empty() {
+            true => String::new(),
+            // notes must be indented deeper than the task to be read back as notes
+            false if indent_width(&note.indent) > indent_width(indent) => {
+                format!("{}{}", note.indent, note.text)
+            }
+            false => format!("{indent}  {}", note.text),
+        }
+    }
+
+    /// Appends a note below the task and its subtasks
+    pub fn add_note(&mut self, text: &str) {
+        self.notes.push(Note {
+            text: text.to_string(),
+            indent: format!("{}  ", self.indent),
+            position: self.subtasks.len(),
+        });
+    }
+
+    /// Appends a subtask, indenting it below this task if needed
+    pub fn add_subtask(&mut self, mut task: Task) {
+        if indent_width(&task.indent) <= indent_width(&self.indent) {
+            task.set_indent(&format!("{}  ", self.indent));
+        }
+        self.subtasks.push(task);
+    }
+
+    /// Attaches an indented line to the last task (recursively) that is indented less than it,
+    /// along with the blank lines above it
+    pub(crate) fn attach_note(tasks: &mut [Task], line: &str, blank_lines: usize) -> bool {
+        let note = line.trim_end();
+        let text = note.trim_start();
+        let indent = &note[..note.len() - text.len()];
+        let Some(last) = tasks.last_mut() else {
+            return false;
+        };
+        if indent_width(indent) <= indent_width(&last.indent) {
+            return false;
+        }
+        let nested = match last.subtasks.last() {
+            Some(subtask) if indent_width(indent) > indent_width(&subtask.indent) => {
+                Task::attach_note(&mut last.subtasks, line, blank_lines)
+            }
+            _ => false,
+        };
+        if !nested {
+            let position = last.subtasks.len();
+            let blank = Note {
+                position,
+                ..Default::default()
+            };
+            last.notes.extend(std::iter::repeat_n(blank, blank_lines));
+            last.notes.push(Note {
+                text: text.to_string(),
+                indent: indent.to_string(),
+                position,
+            });
+        }
+        true
+    }
+
+    /// Changes indentation of this task, notes and subtasks keep their indentation relative to it
+    pub fn set_indent(&mut self, indent: &str) {
+        for note in self.notes.iter_mut().filter(|note| !note.text.is_empty()) {
+            let extra = note
+                .indent
+                .strip_prefix(self.indent.as_str())
+                .unwrap_or("  ")
+                .to_string();
+            note.indent = format!("{indent}{extra}");
+        }
+        for task in &mut self.subtasks {
+            let extra = task
+                .indent
+                .strip_prefix(self.indent.as_str())
+                .unwrap_or("  ")
+                .to_string();
+            task.set_indent(&format!("{indent}{extra}"));
+        }
+        self.indent = indent.to_string();
+    }
+
+    /// Places task in tasks, nesting it under the last task (recursively) if it is indented deeper
+    pub(crate) fn insert_nested(tasks: &mut Vec<Task>, task: Task) {
+        match tasks.last_mut() {
+            Some(last) if indent_width(&task.indent) > indent_width(&last.indent) => {
+                Task::insert_nested(&mut last.subtasks, task)
+            }
+            _ => tasks.push(task),
+        }
+    }
+}
+
+/// Width of indentation in columns, tabs count as 4
+pub(crate) fn indent_width(indent: &str) -> usize {
+    indent.chars().map(|c| if c == '\t' { 4 } else { 1 }).sum()
+}
+
+/// Name of a tag or context without trailing punctuation e.g. `deploy` in `#deploy,`
+fn metadata_name(word: &str) -> Option<&str> {
+    let name = word.trim_end_matches(|c: char| c.is_ascii_punctuation() && c != '_');
+    match name.chars().next() {
+        Some(c) if c.is_alphanumeric() => Some(name),
+        _ => None,
+    }
+}
+
+/// Splits a `key:value` word, keys start with a letter so times (10:30) and urls (https://) are left alone
+fn parse_property(word: &str) -> Option<(&str, &str)> {
+    let (key, value) = word.split_once(':')?;
+    let mut key_chars = key.chars();
+    if !key_chars.next()?.is_alphabetic() {
+        return None;
+    }
+    if !key_chars.all(|c| c.is_alphanumeric() || c == '_' || c == '-') {
+        return None;
+    }
+    if value.is_empty() || value.starts_with('/') {
+        return None;
+    }
+    Some((key, value))
+}
+
+/// Splits a leading checkbox e.g. `[x] task` into its status, its marker and the remaining text
+pub(crate) fn parse_checkbox(s: &str) -> Option<(Status, char, &str)> {
+    let mut chars = s.chars();
+    if chars.next() != Some('[') {
+        return None;
+    }
+    let marker = chars.next()?;
+    let status = Status::from_marker(marker)?;
+    if chars.next() != Some(']') {
+        return None;
+    }
+    let rest = chars.as_str();
+    // checkbox must be followed by a space or end the line
+    if !rest.is_empty() && !rest.starts_with([' ', '\t']) {
+        return None;
+    }
+    Some((status, marker, rest.trim_start()))
 }
 
 impl FromStr for Task {
-    type Err = Box<dyn error::Error + Send + Sync>;
-    fn from_str(s: &str) -> Result<Self> {
-        let s = s.trim();
+    type Err = Error;
+    fn from_str(line: &str) -> Result<Self> {
+        let s = line.trim_end();
+        let trimmed = s.trim_start();
+        let indent = &s[..s.len() - trimmed.len()];
+        let s = trimmed;
 
         let first_char = s.chars().next();
         match first_char {
-            Some('-') => {}                                // this is a task
-            _ => return err!("Unable to parse task: {s}"), // this is not a task
+            Some('-') => {} // this is a task
+            _ => {
+                // this is not a task
+                return Err(Error::InvalidTask {
+                    text: s.to_string(),
+                    location: Location::of_line(0, line),
+                });
+            }
         }
 
+        let text = s.trim_start_matches(['-', ' ', '\t']); // trim - and spaces
+        let task = match parse_checkbox(text) {
+            Some((status, marker, text)) => {
+                let mut task = Task::new(text);
+                task.set_status(status);
+                if marker != status.marker() {
+                    task.marker = Some(marker);
+                }
+                task
+            }
+            None => Task::new(text),
+        };
         Ok(Task {
-            text: s.trim_start_matches(&['-', ' ', '\t']).to_string(), // trim - and spaces
+            indent: indent.to_string(),
+            ..task
         })
     }
 }
+impl Default for Task {
+    fn default() -> Task {
+        Task::new("")
+    }
+}
+
 impl fmt::Display for Task {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let text = self.text.clone();
-        write!(f, "- {text}")
+        write!(f, "{}{}", self.indent, self.line())?;
+        for child in self.children() {
+            match child {
+                Child::Note(note) => write!(f, "\n{}", self.note_line(note))?,
+                Child::Subtask(task) => write!(f, "\n{task}")?,
+            }
+        }
+        Ok(())
     }
 }
 
@@ -41,9 +493,161 @@ mod tests {
     fn parse_task() {
         let task_line = "- pick up dry cleaning";
         let actual: Task = task_line.parse().expect("Unable to parse task");
-        let expected = Task {
-            text: "pick up dry cleaning".to_string(),
-        };
+        let expected = Task::new("pick up dry cleaning");
         assert_eq!(actual, expected);
     }
+
+    #[test]
+    fn parse_task_checkbox() {
+        let open: Task = "- [ ] water plants".parse().expect("Unable to parse task");
+        assert_eq!(open.text, "water plants");
+        assert_eq!(open.status, Status::Open);
+        assert!(open.checkbox);
+
+        let done: Task = "- [x] water plants".parse().expect("Unable to parse task");
+        assert_eq!(done.text, "water plants");
+        assert!(done.is_done());
+
+        // other spellings are kept until the status changes
+        let mut upper: Task = "- [X] water plants".parse().expect("Unable to parse task");
+        assert!(upper.is_done());
+        assert_eq!(upper.marker, Some('X'));
+        upper.set_status(Status::Open);
+        upper.complete();
+        assert_eq!(upper.line(), "- [x] water plants");
+
+        let cancelled: Task = "- [-] water plants".parse().expect("Unable to parse task");
+        assert_eq!(cancelled.text, "water plants");
+        assert_eq!(cancelled.status, Status::Cancelled);
+
+        // brackets that are not a checkbox are part of the text
+        let text: Task = "- [link] water plants"
+            .parse()
+            .expect("Unable to parse task");
+        assert_eq!(text, Task::new("[link] water plants"));
+    }
+
+    #[test]
+    fn task_roundtrip() {
+        let lines = [
+            "- plain task",
+            "- [ ] open task",
+            "- [x] done task",
+            "- [~] task in progress",
+            "- [!] blocked task",
+            "- [-] cancelled task",
+            "- [>] deferred task",
+            "- [X] done task",
+        ];
+        for line in lines {
+            let task: Task = line.parse().expect("Unable to parse task");
+            assert_eq!(task.to_string(), line);
+        }
+    }
+
+    #[test]
+    fn parse_status_name() {
+        for status in Status::ALL {
+            assert_eq!(status.name().parse::<Status>().unwrap(), status);
+        }
+        assert_eq!("in-progress".parse::<Status>().unwrap(), Status::InProgress);
+        assert!("finished".parse::<Status>().is_err());
+    }
+
+    #[test]
+    fn parse_task_metadata() {
+        let line = "- [ ] ship release #deploy, #infra @office due:2024-05-01 est:2h see https://example.com at 10:30";
+        let task: Task = line.parse().expect("Unable to parse task");
+
+        assert_eq!(task.tags, vec!["deploy", "infra"]);
+        assert_eq!(task.contexts, vec!["office"]);
+        assert_eq!(
+            task.properties,
+            BTreeMap::from([
+                ("due".to_string(), "2024-05-01".to_string()),
+                ("est".to_string(), "2h".to_string()),
+            ])
+        );
+        assert_eq!(task.property("due"), Some("2024-05-01"));
+        // text is kept as written
+        assert_eq!(task.to_string(), line);
+    }
+
+    #[test]
+    fn set_task_text() {
+        let mut task = Task::new("call mom @home");
+        task.set_text("call dad #family");
+        assert!(task.contexts.is_empty());
+        assert_eq!(task.tags, vec!["family"]);
+    }
+
+    #[test]
+    fn parse_indented_task() {
+        let task: Task = "    - [x] step 1".parse().expect("Unable to parse task");
+        assert_eq!(task.indent, "    ");
+        assert_eq!(task.text, "step 1");
+        assert_eq!(task.to_string(), "    - [x] step 1");
+    }
+
+    #[test]
+    fn subtasks_roll_up() {
+        let mut parent = Task::new("release");
+        let mut step1 = Task::new("build");
+        step1.complete();
+        parent.add_subtask(step1);
+        parent.add_subtask(Task::new("publish"));
+        assert!(!parent.is_done());
+        assert_eq!(parent.to_string(), "- release\n  - [x] build\n  - publish");
+
+        parent.subtasks[1].set_status(Status::Cancelled);
+        assert!(parent.is_done());
+
+        parent.roll_up();
+        assert_eq!(parent.status, Status::Done);
+        assert_eq!(
+            parent.to_string(),
+            "- [x] release\n  - [x] build\n  - [-] publish"
+        );
+    }
+
+    #[test]
+    fn cancelled_subtasks_dont_roll_up() {
+        let mut parent = Task::new("release");
+        parent.add_subtask(Task::new("build"));
+        parent.subtasks[0].set_status(Status::Cancelled);
+        assert!(!parent.is_done());
+        parent.roll_up();
+        assert_eq!(parent.to_string(), "- release\n  - [-] build");
+
+        // a blocked parent is still counted as done once its subtasks are
+        let mut parent: Task = "- [!] release\n".parse().unwrap();
+        parent.add_subtask("- [x] build".parse().unwrap());
+        assert_eq!(parent.effective_status(), Status::Done);
+    }
+
+    #[test]
+    fn notes_keep_their_place() {
+        let mut task: Task = "- deploy".parse().unwrap();
+        task.add_subtask(Task::new("build"));
+        task.add_note("then ship it");
+        task.subtasks[0].add_note("cargo build");
+        assert_eq!(
+            task.to_string(),
+            "- deploy\n  - build\n    cargo build\n  then ship it"
+        );
+
+        task.set_indent("\t");
+        assert_eq!(
+            task.to_string(),
+            "\t- deploy\n\t  - build\n\t    cargo build\n\t  then ship it"
+        );
+    }
+
+    #[test]
+    fn complete_task() {
+        let mut task = Task::new("water plants");
+        task.complete();
+        assert!(task.is_done());
+        assert_eq!(task.to_string(), "- [x] water plants");
+    }
 }
@@ -0,0 +1,549 @@
+//! [todo.txt](http://todotxt.org) import and export
+//!
+//! Every task is a line of todo.txt, e.g. `(A) 2024-03-01 deploy @office +Work`, newest first:
+//!
+//! - the section of a task is the last `+project` of its line, with spaces, tabs and `%` written as
+//!   `%20`, `%09` and `%25` e.g. `+My%20Home`. Tasks of anonymous sections have none, so a project
+//!   in their text is read back as their section
+//! - the creation date is the first day a task appears on and the completion date the first day it
+//!   appears done. Imported tasks go to the day they were completed on, or created on, or today
+//! - priorities are kept as a `pri:A` property, as todo.txt does for completed tasks
+//! - statuses other than open and done are kept as a `status:blocked` property, cancelled tasks are
+//!   also marked as completed
+//! - `@contexts` and other `key:value` properties are part of the text in both formats
+//! - completed tasks that were created on another day keep that date as a `created:2024-03-01`
+//!   property when imported
+//! - subtasks follow their task with a `parent:` property set to the `id:` of the task, which is
+//!   added to tasks that have subtasks or notes and no id. Notes are lines starting with `note:` and
+//!   the id of their task, e.g. `note:5f3a2c1 after the review`
+//!
+//! Imported subtasks and notes are put below the task they point at, in the order they are read.
+
+use crate::{
+    address::stable_hash,
+    task::{Child, Task},
+    Day, Status, Todo,
+};
+use chrono::NaiveDate;
+use itertools::Itertools;
+use std::{cmp::Reverse, collections::HashMap};
+
+/// Section name and the texts of a task and the tasks above it, a task is the same task on every
+/// day it has the same key
+type Key<'a> = (&'a str, Vec<&'a str>);
+
+/// Days tasks were created and completed on, by key
+#[derive(Default)]
+struct History<'a> {
+    created: HashMap<Key<'a>, NaiveDate>,
+    completed: HashMap<Key<'a>, NaiveDate>,
+}
+
+impl<'a> History<'a> {
+    /// Records task, which is in section and below the tasks of path, appearing on date
+    fn record(&mut self, date: NaiveDate, section: &'a str, path: &[&'a str], task: &'a Task) {
+        let mut path = path.to_vec();
+        path.push(&task.text);
+        let key = (section, path.clone());
+        self.created.entry(key.clone()).or_insert(date);
+        if is_completed(task) {
+            self.completed.entry(key).or_insert(date);
+        } else {
+            // reopened tasks are completed again later
+            self.completed.remove(&key);
+        }
+        for subtask in &task.subtasks {
+            self.record(date, section, &path, subtask);
+        }
+    }
+
+    /// Days task, which is in section and below the tasks of path, was created and completed on
+    fn dates(&self, section: &str, path: &[&str], task: &Task) -> (NaiveDate, Option<NaiveDate>) {
+        let mut path = path.to_vec();
+        path.push(&task.text);
+        let key = (section, path);
+        let created = task
+            .property("created")
+            .and_then(|created| NaiveDate::parse_from_str(created, "%Y-%m-%d").ok())
+            .unwrap_or(self.created[&key]);
+        (created, self.completed.get(&key).copied())
+    }
+}
+
+/// todo.txt file with the newest copy of every task of todo
+pub fn to_string(todo: &Todo) -> String {
+    let days: Vec<&Day> = todo.days.values().sorted_by_key(|day| day.date).collect();
+
+    // tasks are carried from day to day, a task is the same task on every day it appears
+    let mut history = History::default();
+    let mut newest: HashMap<(&str, &str), &Task> = HashMap::new();
+    let mut order: Vec<(&str, &str)> = Vec::new();
+    for day in &days {
+        for section in &day.sections {
+            for task in &section.tasks {
+                let key = (section.name.as_str(), task.text.as_str());
+                if newest.insert(key, task).is_none() {
+                    order.push(key);
+                }
+                history.record(day.date, &section.name, &[], task);
+            }
+        }
+    }
+
+    // newest first by the day tasks are imported to, in the order they first appear within a day
+    let placed = |key: &(&str, &str)| {
+        let (created, completed) = history.dates(key.0, &[], newest[key]);
+        completed.unwrap_or(created)
+    };
+    let mut lines = Vec::new();
+    for key in order.iter().sorted_by_key(|key| Reverse(placed(key))) {
+        write_task(&mut lines, &history, key.0, &[], newest[key], None);
+    }
+    lines.iter().map(|line| format!("{line}\n")).collect()
+}
+
+/// Pushes the lines of task, its subtasks and its notes to lines
+fn write_task(
+    lines: &mut Vec<String>,
+    history: &History,
+    section: &str,
+    path: &[&str],
+    task: &Task,
+    parent: Option<&str>,
+) {
+    let (created, completed) = history.dates(section, path, task);
+    let mut path = path.to_vec();
+    path.push(&task.text);
+
+    let has_children =
+        !task.subtasks.is_empty() || task.notes.iter().any(|note| !note.text.is_empty());
+    let id = match task.property("id") {
+        Some(id) => Some(id.to_string()),
+        None if has_children => {
+            let hash = stable_hash(&format!("{section}/{}", path.join("/")));
+            Some(format!("{hash:016x}")[..7].to_string())
+        }
+        None => None,
+    };
+    lines.push(format_task(
+        section,
+        task,
+        created,
+        completed,
+        id.as_deref(),
+        parent,
+    ));
+
+    let Some(id) = id else {
+        return;
+    };
+    for child in task.children() {
+        match child {
+            Child::Note(note) if note.text.is_empty() => {}
+            Child::Note(note) => lines.push(format!("note:{id} {}", note.text)),
+            Child::Subtask(subtask) => {
+                write_task(lines, history, section, &path, subtask, Some(&id))
+            }
+        }
+    }
+}
+
+fn format_task(
+    section: &str,
+    task: &Task,
+    created: NaiveDate,
+    completed: Option<NaiveDate>,
+    id: Option<&str>,
+    parent: Option<&str>,
+) -> String {
+    let mut words = Vec::new();
+    let priority = task.property("pri").filter(|pri| is_priority(pri));
+    match completed {
+        Some(completed) => words.push(format!("x {}", completed.format("%Y-%m-%d"))),
+        None => words.extend(priority.map(|pri| format!("({pri})"))),
+    }
+    words.push(created.format("%Y-%m-%d").to_string());
+
+    // open tasks have the priority in front instead, the creation date is always in front and the
+    // id always at the end
+    let created_property = format!("created:{}", created.format("%Y-%m-%d"));
+    let id = id.map(|id| format!("id:{id}"));
+    let text = task.text.split_whitespace().filter(|word| {
+        *word != created_property
+            && id.as_deref() != Some(*word)
+            && (completed.is_some() || priority.is_none_or(|pri| *word != format!("pri:{pri}")))
+    });
+    words.extend(text.map(str::to_string));
+
+    // the section is the last project, even if the text has the same one
+    if !section.is_empty() {
+        words.push(format!("+{}", encode_project(section)));
+    }
+    let status = match task.status {
+        Status::Open | Status::Done => None,
+        status => Some(format!("status:{status}")),
+    };
+    let parent = parent.map(|parent| format!("parent:{parent}"));
+    words.extend([status, id, parent].into_iter().flatten());
+    words.join(" ")
+}
+
+/// Todo with the tasks of a todo.txt file, tasks without dates are added to today
+pub fn from_str(s: &str, today: NaiveDate) -> Todo {
+    let mut todo = Todo::default();
+    // where the tasks with an id are: day, section and indexes of the task and the tasks above it
+    let mut ids: HashMap<String, (NaiveDate, String, Vec<usize>)> = HashMap::new();
+    for line in s.lines().filter(|line| !line.trim().is_empty()) {
+        let line = line.trim();
+        if let Some((id, note)) = line
+            .strip_prefix("note:")
+            .and_then(|line| line.split_once(char::is_whitespace))
+        {
+            if let Some(task) = ids.get(id).and_then(|place| task_mut(&mut todo, place)) {
+                task.add_note(note.trim_start());
+                continue;
+            }
+        }
+
+        let (date, section, mut task) = parse_line(line, today);
+        let id = task.property("id").map(str::to_string);
+        let parent = task
+            .property("parent")
+            .and_then(|parent| Some((parent.to_string(), ids.get(parent)?.clone())));
+        let place = match parent {
+            Some((parent, (date, section, mut path))) => {
+                let text = task
+                    .text
+                    .split_whitespace()
+                    .filter(|word| *word != format!("parent:{parent}"))
+                    .join(" ");
+                task.set_text(&text);
+                let parent = task_mut(&mut todo, &(date, section.clone(), path.clone()))
+                    .expect("ids point at imported tasks");
+                path.push(parent.subtasks.len());
+                parent.add_subtask(task);
+                (date, section, path)
+            }
+            None => {
+                let day = todo.days.entry(date).or_insert_with(|| Day {
+                    planned: date > today,
+                    ..Day::new(date)
+                });
+                let tasks = &mut day.section_mut(&section).tasks;
+                tasks.push(task);
+                (date, section, vec![tasks.len() - 1])
+            }
+        };
+        if let Some(id) = id {
+            ids.insert(id, place);
+        }
+    }
+    todo
+}
+
+/// Task at place, see [from_str]
+fn task_mut<'a>(
+    todo: &'a mut Todo,
+    (date, section, path): &(NaiveDate, String, Vec<usize>),
+) -> Option<&'a mut Task> {
+    let section = todo
+        .days
+        .get_mut(date)?
+        .sections
+        .iter_mut()
+        .find(|s| s.name == *section)?;
+    let (first, rest) = path.split_first()?;
+    let mut task = section.tasks.get_mut(*first)?;
+    for index in rest {
+        task = task.subtasks.get_mut(*index)?;
+    }
+    Some(task)
+}
+
+/// Day, section and task of a todo.txt line
+fn parse_line(line: &str, today: NaiveDate) -> (NaiveDate, String, Task) {
+    let mut words: Vec<&str> = line.split_whitespace().collect();
+    let date =
+        |word: Option<&&str>| word.and_then(|w| NaiveDate::parse_from_str(w, "%Y-%m-%d").ok());
+
+    let done = words.first() == Some(&"x");
+    let mut completed = None;
+    if done {
+        words.remove(0);
+        completed = date(words.first());
+        if completed.is_some() {
+            words.remove(0);
+        }
+    }
+    let mut priority = None;
+    if let Some(pri) = words
+        .first()
+        .and_then(|word| word.strip_prefix('(')?.strip_suffix(')'))
+        .filter(|pri| is_priority(pri))
+    {
+        priority = Some(pri.to_string());
+        words.remove(0);
+    }
+    let created = date(words.first());
+    if created.is_some() {
+        words.remove(0);
+    }
+
+    // the last project is the section
+    let project = words
+        .iter()
+        .rposition(|word| word.strip_prefix('+').is_some_and(|p| !p.is_empty()));
+    let section = match project {
+        Some(index) => decode_project(&words.remove(index)[1..]),
+        None => String::new(),
+    };
+    let mut status = None;
+    let mut text = Vec::new();
+    for word in words {
+        if let Some(name) = word.strip_prefix("status:") {
+            if let Ok(parsed) = name.parse::<Status>() {
+                status = Some(parsed);
+                continue;
+            }
+        }
+        text.push(word.to_string());
+    }
+    if let Some(pri) = priority {
+        let pri = format!("pri:{pri}");
+        if !text.contains(&pri) {
+            text.push(pri);
+        }
+    }
+    let date = completed.or(created).unwrap_or(today);
+    // completed tasks go to the day they were completed on, the day they were created on is kept
+    if let Some(created) = created.filter(|created| *created != date) {
+        let created = format!("created:{}", created.format("%Y-%m-%d"));
+        if !text.iter().any(|word| word.starts_with("created:")) {
+            text.push(created);
+        }
+    }
+
+    let mut task = Task::new(&text.join(" "));
+    match (done, status) {
+        (_, Some(status)) => task.set_status(status),
+        (true, None) => task.complete(),
+        (false, None) => {}
+    }
+    (date, section, task)
+}
+
+/// Section name as a project, whitespace would end it
+fn encode_project(section: &str) -> String {
+    section
+        .replace('%', "%25")
+        .replace(' ', "%20")
+        .replace('\t', "%09")
+}
+
+/// Section name of a project written by [encode_project]
+fn decode_project(project: &str) -> String {
+    let mut out = String::new();
+    let mut rest = project;
+    while let Some(index) = rest.find('%') {
+        out.push_str(&rest[..index]);
+        rest = &rest[index..];
+        let decoded = match rest.get(..3) {
+            Some("%20") => ' ',
+            Some("%09") => '\t',
+            Some("%25") => '%',
+            _ => {
+                out.push('%');
+                rest = &rest[1..];
+                continue;
+            }
+        };
+        out.push(decoded);
+        rest = &rest[3..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Done and cancelled tasks are completed in todo.txt
+fn is_completed(task: &Task) -> bool {
+    task.is_done() || task.status == Status::Cancelled
+}
+
+/// Single uppercase letter, like the `A` of `(A)`
+fn is_priority(s: &str) -> bool {
+    s.len() == 1 && s.chars().all(|c| c.is_ascii_uppercase())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use indoc::indoc;
+
+    fn date(d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 3, d).unwrap()
+    }
+
+    #[test]
+    fn export_todotxt() {
+        let todo: Todo = indoc! {"
+            [2024-03-07]
+            Work
+            - [x] deploy @office pri:A
+            - [!] review pri:B
+            Home
+            - [x] groceries
+
+            [2024-03-06]
+            Work
+            - deploy @office pri:A
+            - [!] review pri:B
+            - call
+
+            [2024-03-05]
+            Home
+            - [x] groceries
+            - laundry
+        "}
+        .parse()
+        .unwrap();
+        let expected = indoc! {"
+            (B) 2024-03-06 review +Work status:blocked
+            2024-03-06 call +Work
+            x 2024-03-07 2024-03-06 deploy @office pri:A +Work
+            2024-03-05 laundry +Home
+            x 2024-03-05 2024-03-05 groceries +Home
+        "};
+        let exported = to_string(&todo);
+        let mut actual: Vec<&str> = exported.lines().collect();
+        let mut expected: Vec<&str> = expected.lines().collect();
+        actual.sort();
+        expected.sort();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn import_todotxt() {
+        let todotxt = indoc! {"
+            x 2024-03-07 2024-03-01 deploy +Work @office pri:A
+            (B) 2024-03-06 review +Release +Work status:in-progress
+            call mom
+
+            2024-03-09 plan trip +Home
+        "};
+        let todo = from_str(todotxt, date(8));
+        let day = |d| &todo.days[&date(d)];
+
+        let deploy = &day(7).sections[0];
+        assert_eq!(deploy.name, "Work");
+        assert_eq!(
+            deploy.tasks[0].line(),
+            "- [x] deploy @office pri:A created:2024-03-01"
+        );
+        assert_eq!(deploy.tasks[0].contexts, vec!["office"]);
+
+        let review = &day(6).sections[0].tasks[0];
+        assert_eq!(review.line(), "- [~] review +Release pri:B");
+        assert_eq!(day(8).sections[0].name, "");
+        assert_eq!(day(8).sections[0].tasks[0].text, "call mom");
+        assert!(day(9).planned);
+        assert!(!day(7).planned);
+
+        let exported = to_string(&todo);
+        assert!(exported.contains("x 2024-03-07 2024-03-01 deploy @office pri:A +Work"));
+    }
+
+    #[test]
+    fn todotxt_round_trip() {
+        let todo: Todo = indoc! {"
+            [2024-03-07]
+            Work
+            - [~] review +Release pri:B
+            - [x] deploy +Work
+            My Home
+            - groceries
+            - [x] laundry 100%
+
+            [2024-03-06]
+            - call mom +Family
+            Work
+            - deploy +Work
+            - [x] write docs
+        "}
+        .parse()
+        .unwrap();
+        let exported = to_string(&todo);
+        let expected = indoc! {"
+            x 2024-03-07 2024-03-06 deploy +Work +Work
+            (B) 2024-03-07 review +Release +Work status:in-progress
+            2024-03-07 groceries +My%20Home
+            x 2024-03-07 2024-03-07 laundry 100% +My%20Home
+            2024-03-06 call mom +Family
+            x 2024-03-06 2024-03-06 write docs +Work
+        "};
+        assert_eq!(exported, expected);
+
+        let imported = from_str(&exported, date(8));
+        let review = &imported.days[&date(7)].sections[0];
+        assert_eq!(review.name, "Work");
+        assert_eq!(review.tasks[0].text, "deploy +Work created:2024-03-06");
+        assert_eq!(review.tasks[1].text, "review +Release pri:B");
+        assert_eq!(imported.days[&date(7)].sections[1].name, "My Home");
+        assert_eq!(to_string(&imported), exported);
+    }
+
+    #[test]
+    fn project_names() {
+        for name in ["Work", "My Home", "50% done", "a\tb", "%20"] {
+            assert_eq!(decode_project(&encode_project(name)), name);
+        }
+        assert_eq!(decode_project("100%"), "100%");
+    }
+
+    #[test]
+    fn todotxt_subtasks_and_notes() {
+        let todo: Todo = indoc! {"
+            [2024-03-07]
+            Work
+            - deploy
+              after the review
+              - [-] build
+                - lint
+              on friday
+            - review id:rev
+              - ask bob
+        "}
+        .parse()
+        .unwrap();
+        let exported = to_string(&todo);
+        let id = format!("{:016x}", stable_hash("Work/deploy"))[..7].to_string();
+        let build = format!("{:016x}", stable_hash("Work/deploy/build"))[..7].to_string();
+        let expected = format!(
+            "2024-03-07 deploy +Work id:{id}\n\
+             note:{id} after the review\n\
+             x 2024-03-07 2024-03-07 build +Work status:cancelled id:{build} parent:{id}\n\
+             2024-03-07 lint +Work parent:{build}\n\
+             note:{id} on friday\n\
+             2024-03-07 review +Work id:rev\n\
+             2024-03-07 ask bob +Work parent:rev\n"
+        );
+        assert_eq!(exported, expected);
+
+        let imported = from_str(&exported, date(8));
+        let expected = format!(
+            indoc! {"
+                [2024-03-07]
+                Work
+                - deploy id:{}
+                  after the review
+                  - [-] build id:{}
+                    - lint
+                  on friday
+                - review id:rev
+                  - ask bob"},
+            id, build
+        );
+        assert_eq!(imported.to_string(), expected);
+        assert_eq!(to_string(&imported), exported);
+    }
+}